@@ -4,12 +4,11 @@ use derive_more::From;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "tracing")]
-use tracing;
 
 use crate::{
     amount::Amount,
     error::Error,
+    event::{AccountEvent, AccountEventKind, AccountEvents, Balances},
     transaction::{
         ChargeBack, Deposit, Dispute, Resolve, Transaction, TransactionId, TransactionKind,
         Withdraw,
@@ -17,7 +16,7 @@ use crate::{
 };
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(PartialEq, Eq, Hash, Clone, Debug, From)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug, From)]
 pub struct AccountId(pub u16);
 
 #[derive(Debug, Clone)]
@@ -117,7 +116,7 @@ impl Account {
             (deposit, DisputeState::NotDisputed),
         );
 
-        debug_assert!(matches!(is_deposit_unique, None));
+        debug_assert!(is_deposit_unique.is_none());
 
         Ok(())
     }
@@ -131,7 +130,8 @@ impl Account {
         Ok(())
     }
 
-    fn try_apply_dispute(&mut self, dispute: &Dispute) -> Result<(), Error> {
+    /// Returns the disputed amount.
+    fn try_apply_dispute(&mut self, dispute: &Dispute) -> Result<Amount, Error> {
         // deposit should not be mutated
         let (deposit, dispute_state) = self
             .deposits
@@ -161,14 +161,15 @@ impl Account {
 
                 *dispute_state = DisputeState::Disputed;
 
-                Ok(())
+                Ok(deposit.to_amount())
             }
             DisputeState::Disputed => Err(Error::AlreadyDisputed),
             DisputeState::ChargedBack => Err(Error::AlreadyChargedBack),
         }
     }
 
-    fn try_apply_resolve(&mut self, resolve: &Resolve) -> Result<(), Error> {
+    /// Returns the released amount.
+    fn try_apply_resolve(&mut self, resolve: &Resolve) -> Result<Amount, Error> {
         // todo deposit should not be modified
         let (deposit, dispute_status) = self
             .deposits
@@ -177,29 +178,34 @@ impl Account {
 
         match dispute_status {
             DisputeState::Disputed => {
-                self.state.available = self
+                let available = self
                     .state
                     .available
                     .checked_add(deposit.amount())
                     .ok_or(Error::ResolveOverflow)?;
 
-                // TODO: except note
-                // The unwrap is fine here since there can be only one
-                // active dispute on a transaction. That means that
-                // this is the only resolution ever applied to that
-                // for that dispute, effectively reversing it's effect.
-                // held value has no other ways of changing.
-                self.state.held = self.state.held.checked_sub(deposit.amount()).unwrap();
+                // There can be only one active dispute on a transaction. That means
+                // that this is the only resolution ever applied to that dispute,
+                // effectively reversing it's effect. Held value has no other ways
+                // of changing, so the underflow is not expected.
+                let held = self
+                    .state
+                    .held
+                    .checked_sub(deposit.amount())
+                    .ok_or(Error::HeldUnderflow)?;
 
+                self.state.available = available;
+                self.state.held = held;
                 *dispute_status = DisputeState::NotDisputed;
-                Ok(())
+                Ok(deposit.to_amount())
             }
             DisputeState::NotDisputed => Err(Error::TargetNotDisputed),
             DisputeState::ChargedBack => Err(Error::AlreadyChargedBack),
         }
     }
 
-    fn try_apply_charge_back(&mut self, charge_back: &ChargeBack) -> Result<(), Error> {
+    /// Returns the charged back amount.
+    fn try_apply_charge_back(&mut self, charge_back: &ChargeBack) -> Result<Amount, Error> {
         // deposit should not be modified
         let (deposit, dispute_status) = self
             .deposits
@@ -208,21 +214,16 @@ impl Account {
 
         match dispute_status {
             DisputeState::Disputed => {
+                // Underflow is not expected, see `try_apply_resolve`.
                 self.state.held = self
                     .state
                     .held
                     .checked_sub(deposit.amount())
-                    // TODO: except note
-                    // The unwrap is fine here since there can be only one
-                    // active dispute on a transaction. That means that
-                    // this is the only resolution ever applied to that
-                    // for that dispute, effectively reversing it's effect.
-                    // held value has no other ways of changing.
-                    .unwrap();
+                    .ok_or(Error::HeldUnderflow)?;
 
                 *dispute_status = DisputeState::ChargedBack;
                 self.state.is_locked = IsLocked::Locked;
-                Ok(())
+                Ok(deposit.to_amount())
             }
             DisputeState::NotDisputed => Err(Error::TargetNotDisputed),
             DisputeState::ChargedBack => Err(Error::AlreadyChargedBack),
@@ -267,9 +268,19 @@ impl Account {
         self.state
     }
 
+    /// Whether a deposit with the tx id was applied to the account.
+    pub(crate) fn has_deposit(&self, tx_id: &TransactionId) -> bool {
+        self.deposits.contains_key(tx_id)
+    }
+
+    /// Apply the transaction, returning the events describing the state change.
+    ///
     /// TODO: better name
     #[cfg_attr(feature = "tracing", tracing::instrument(err(Display)))]
-    pub fn try_apply_transaction(&mut self, transaction_request: Transaction) -> Result<(), Error> {
+    pub fn try_apply_transaction(
+        &mut self,
+        transaction_request: Transaction,
+    ) -> Result<AccountEvents, Error> {
         if self.state().is_locked == IsLocked::Locked {
             return Err(Error::LockedAccount);
         }
@@ -277,46 +288,88 @@ impl Account {
         // NOTE:
         // not checking tx_id uniqueness, as the assignment does not mandate it.
 
-        // TODO: log state change!
         // TODO: refactor into handle / apply api to make the transactional nature
         // of these operations more obvious
+        let before = Balances::from(&self.state);
 
         // handle tx
-        match transaction_request.kind {
-            TransactionKind::Deposit(ref deposit) => self.try_apply_deposit(deposit.clone())?,
-            TransactionKind::Withdraw(ref withdraw) => self.try_apply_withdraw(withdraw)?,
-            TransactionKind::Dispute(ref dispute) => self.try_apply_dispute(dispute)?,
-            TransactionKind::Resolve(ref resolve) => self.try_apply_resolve(resolve)?,
+        let (tx_id, kind) = match transaction_request.kind {
+            TransactionKind::Deposit(ref deposit) => {
+                self.try_apply_deposit(deposit.clone())?;
+                let amount = deposit.to_amount();
+                (deposit.to_tx_id(), AccountEventKind::Deposited { amount })
+            }
+            TransactionKind::Withdraw(ref withdraw) => {
+                self.try_apply_withdraw(withdraw)?;
+                let amount = withdraw.to_amount();
+                (withdraw.to_tx_id(), AccountEventKind::Withdrawn { amount })
+            }
+            TransactionKind::Dispute(ref dispute) => {
+                let amount = self.try_apply_dispute(dispute)?;
+                let tx_id = dispute.target_tx_id.clone();
+                (tx_id, AccountEventKind::FundsHeld { amount })
+            }
+            TransactionKind::Resolve(ref resolve) => {
+                let amount = self.try_apply_resolve(resolve)?;
+                let tx_id = resolve.target_tx_id.clone();
+                (tx_id, AccountEventKind::FundsReleased { amount })
+            }
             TransactionKind::ChargeBack(ref charge_back) => {
-                self.try_apply_charge_back(charge_back)?;
+                let amount = self.try_apply_charge_back(charge_back)?;
+                let tx_id = charge_back.target_tx_id.clone();
+                (tx_id, AccountEventKind::ChargedBack { amount })
             }
         };
 
+        let is_charge_back = matches!(kind, AccountEventKind::ChargedBack { .. });
+        let events = AccountEvents::new(AccountEvent {
+            account_id: self.state.id.clone(),
+            tx_id,
+            kind,
+            before,
+            after: Balances::from(&self.state),
+        });
+        let events = if is_charge_back {
+            events.with_lock()
+        } else {
+            events
+        };
+
         #[cfg(feature = "tracing")]
-        tracing::info!(transaction = ?transaction_request, "Transaction applied");
+        tracing::info!(transaction = ?transaction_request, events = ?events, "Transaction applied");
 
-        Ok(())
+        Ok(events)
     }
 }
 
 #[cfg(feature = "serde")]
-mod account_state_record {
-    use super::{AccountState, Amount};
+pub(crate) mod account_state_record {
+    use super::{AccountId, AccountState, Amount, IsLocked};
+    use crate::Error;
     use serde::Serialize;
 
-    #[derive(Serialize)]
-    struct AccountStateRecord {
-        #[serde(flatten)]
-        account_state: AccountState,
+    /// Output representation of the account state. Fields are listed explicitly,
+    /// instead of flattening the state, since csv does not support serializing maps.
+    #[derive(Debug, Serialize)]
+    pub struct AccountStateRecord {
+        client: AccountId,
+        available: Amount,
+        held: Amount,
         total: Amount,
+        locked: IsLocked,
     }
 
-    impl From<AccountState> for AccountStateRecord {
-        fn from(account_state: AccountState) -> Self {
-            AccountStateRecord {
-                total: account_state.total().unwrap(),
-                account_state,
-            }
+    impl TryFrom<AccountState> for AccountStateRecord {
+        type Error = Error;
+
+        fn try_from(account_state: AccountState) -> Result<Self, Self::Error> {
+            Ok(AccountStateRecord {
+                total: account_state.total()?,
+                client: account_state.id,
+                available: account_state.available,
+                held: account_state.held,
+                locked: account_state.is_locked,
+            })
         }
     }
 }
@@ -40,6 +40,50 @@ impl Amount {
         Ok(Amount(inner))
     }
 
+    /// Builds the amount `mantissa * 10^-scale`, rounded to [`Amount::DECIMAL_POINTS`].
+    ///
+    /// # Errors
+    /// Error::AmountOutOfBounds if the rounded amount is larger than [`Amount::MAX`],
+    /// or the mantissa and scale can't be represented as a 96 bit decimal.
+    pub fn from_fixed_point(mantissa: u128, scale: u32) -> Result<Amount, Error> {
+        let mantissa = i128::try_from(mantissa).map_err(|_e| Error::AmountOutOfBounds)?;
+        let inner = Decimal::try_from_i128_with_scale(mantissa, scale)
+            .map_err(|_e| Error::AmountOutOfBounds)?
+            .round_dp_with_strategy(Self::DECIMAL_POINTS, Self::ROUNDING_STRATEGY);
+
+        if inner > Self::MAX.0 {
+            return Err(Error::AmountOutOfBounds);
+        }
+        Ok(Amount(inner))
+    }
+
+    /// Mantissa of the amount at [`Amount::DECIMAL_POINTS`] scale. Inverse of
+    /// [`Amount::from_fixed_point`].
+    pub fn to_fixed_point(&self) -> u128 {
+        let mut inner = self.0;
+        inner.rescale(Self::DECIMAL_POINTS);
+        inner.mantissa().unsigned_abs()
+    }
+
+    /// Decimal representation with exactly [`Amount::DECIMAL_POINTS`] decimals.
+    pub fn to_fixed_string(&self) -> String {
+        format!("{:.*}", Self::DECIMAL_POINTS as usize, self.0)
+    }
+
+    /// Decimal representation of a mantissa at [`Amount::DECIMAL_POINTS`] scale, with
+    /// exactly [`Amount::DECIMAL_POINTS`] decimals. Sums of amounts may exceed
+    /// [`Amount::MAX`], so they are formatted from their [`Amount::to_fixed_point`] sum.
+    pub fn fixed_point_to_string(mantissa: u128) -> String {
+        let scale = 10u128.pow(Self::DECIMAL_POINTS);
+
+        format!(
+            "{}.{:0width$}",
+            mantissa / scale,
+            mantissa % scale,
+            width = Self::DECIMAL_POINTS as usize
+        )
+    }
+
     pub fn checked_add(&self, rhs: &Amount) -> Option<Amount> {
         self.0
             .checked_add(rhs.0)
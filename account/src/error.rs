@@ -1,10 +1,10 @@
 use thiserror::Error;
 
-use crate::{Amount, Deposit, Withdraw};
+use crate::{AccountEvent, AccountState, Amount, Deposit, LedgerAccount, Totals, Withdraw};
 
 // TODO: all errors should have the tx that caused them. and provide acc info
 // fix this after you introduce the tracing app.
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, Error, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Error {
     // Generic transaction application errors
     #[error("Account is locked. Transactions are not accepted")]
@@ -21,6 +21,8 @@ pub enum Error {
     DisputeOverflow,
     #[error("Total funds overflow")]
     TotalOverflow,
+    #[error("Held funds can't be less than the disputed amount")]
+    HeldUnderflow,
     #[error("Not enough available funds to make a withdraw")]
     InsufficientFundsForWithdraw,
     #[error("Not enough available funds to make a dispute")]
@@ -46,6 +48,10 @@ pub enum Error {
     InsufficientDepositAmount,
     #[error("Minimum allowed value for Withdraw is {:#?}", Withdraw::MIN)]
     InsufficientWithdrawAmount,
+    #[error("Deposits and withdrawals must specify an amount")]
+    MissingAmount,
+    #[error("Transaction type must be one of deposit, withdraw, dispute, resolve, chargeback")]
+    UnknownTransactionType,
 
     #[error(
         "Amount has to be in the interval <{:#?}, {:#?}]",
@@ -54,3 +60,38 @@ pub enum Error {
     )]
     AmountOutOfBounds,
 }
+
+/// Errors of replaying an account event journal.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ReplayError {
+    #[error("Event {position} can't be replayed: {error}")]
+    Rejected { position: usize, error: Error },
+    #[error("Event {position} does not match the replayed event {replayed:?}")]
+    Mismatch {
+        position: usize,
+        replayed: Box<AccountEvent>,
+    },
+    #[error("Event {position} must follow the event of its transaction")]
+    UnexpectedEvent { position: usize },
+    #[error("Journal ends before the last transaction's events")]
+    Truncated,
+    #[error("Stored account state does not match the replayed state {replayed:?}")]
+    StateMismatch { replayed: Box<AccountState> },
+}
+
+/// Errors of the double-entry ledger.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum LedgerError {
+    #[error("Ledger totals overflow")]
+    Overflow,
+    #[error("Ledger settlement {settlement:?} does not match the account balances {balances:?}")]
+    SettlementMismatch { settlement: Totals, balances: u128 },
+    #[error(
+        "Ledger account {account:?} with {totals:?} does not match the account balance {balance:?}"
+    )]
+    BalanceMismatch {
+        account: LedgerAccount,
+        totals: Totals,
+        balance: Amount,
+    },
+}
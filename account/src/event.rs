@@ -0,0 +1,115 @@
+use std::{iter, option};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    account::{AccountId, AccountState},
+    amount::Amount,
+    transaction::TransactionId,
+};
+
+/// Funds of an account.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Balances {
+    pub available: Amount,
+    pub held: Amount,
+}
+
+impl From<&AccountState> for Balances {
+    fn from(state: &AccountState) -> Self {
+        Balances {
+            available: state.available.clone(),
+            held: state.held.clone(),
+        }
+    }
+}
+
+/// Change of the account caused by an applied transaction.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountEventKind {
+    /// Funds were added to the available funds.
+    Deposited { amount: Amount },
+    /// Funds were removed from the available funds.
+    Withdrawn { amount: Amount },
+    /// Funds of a disputed deposit were moved from the available to the held funds.
+    FundsHeld { amount: Amount },
+    /// Funds of a resolved dispute were moved from the held to the available funds.
+    FundsReleased { amount: Amount },
+    /// Held funds of a charged back deposit were removed.
+    ChargedBack { amount: Amount },
+    /// The account was locked. Locked accounts reject all further transactions.
+    AccountLocked,
+}
+
+impl AccountEventKind {
+    /// Amount moved by the change. `None` for the locking of the account.
+    pub fn amount(&self) -> Option<&Amount> {
+        match self {
+            AccountEventKind::Deposited { amount }
+            | AccountEventKind::Withdrawn { amount }
+            | AccountEventKind::FundsHeld { amount }
+            | AccountEventKind::FundsReleased { amount }
+            | AccountEventKind::ChargedBack { amount } => Some(amount),
+            AccountEventKind::AccountLocked => None,
+        }
+    }
+}
+
+/// State change of an account, with the balances before and after the change.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountEvent {
+    pub account_id: AccountId,
+    /// Transaction the event refers to. Disputes, resolutions and charge backs
+    /// refer to the disputed deposit.
+    pub tx_id: TransactionId,
+    pub kind: AccountEventKind,
+    pub before: Balances,
+    pub after: Balances,
+}
+
+/// Events of a single applied transaction, in the order they happened.
+/// A charge back is followed by the locking of the account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountEvents {
+    event: AccountEvent,
+    locked: Option<AccountEvent>,
+}
+
+impl AccountEvents {
+    pub(crate) fn new(event: AccountEvent) -> Self {
+        AccountEvents {
+            event,
+            locked: None,
+        }
+    }
+
+    /// Event of the transaction, without the locking of the account.
+    pub fn event(&self) -> &AccountEvent {
+        &self.event
+    }
+
+    /// Follow the event by the locking of the account.
+    pub(crate) fn with_lock(mut self) -> Self {
+        self.locked = Some(AccountEvent {
+            account_id: self.event.account_id.clone(),
+            tx_id: self.event.tx_id.clone(),
+            kind: AccountEventKind::AccountLocked,
+            before: self.event.after.clone(),
+            after: self.event.after.clone(),
+        });
+        self
+    }
+}
+
+impl IntoIterator for AccountEvents {
+    type Item = AccountEvent;
+    type IntoIter = iter::Chain<iter::Once<AccountEvent>, option::IntoIter<AccountEvent>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        iter::once(self.event).chain(self.locked)
+    }
+}
@@ -0,0 +1,164 @@
+//! Property based tests. Random transaction sequences are applied to a single account
+//! and the account invariants are checked after every step.
+
+use std::collections::{HashMap, HashSet};
+
+use proptest::prelude::*;
+
+use crate::{
+    Account, AccountId, Amount, Balances, Error, IsLocked, Ledger, Transaction, TransactionId,
+};
+use crate::{ChargeBack, Deposit, Dispute, Resolve, TransactionKind, Withdraw};
+
+/// Amounts are mostly small, with the occasional amount close to `Amount::MAX`
+/// so overflows are exercised.
+fn amount() -> impl Strategy<Value = Amount> {
+    prop_oneof![
+        8 => (1..1_000u64).prop_map(Amount::from_u64),
+        1 => Just(Amount::MAX),
+        1 => Just(Amount::MAX.checked_sub(&Amount::from_u64(1_000)).unwrap()),
+    ]
+}
+
+/// Transaction at the given index of the sequence. Deposits and withdrawals use the index
+/// as their id. Secondary transactions target any id in the sequence, including
+/// ids of withdrawals, other secondary transactions and future transactions.
+fn transaction(index: u32, len: u32) -> impl Strategy<Value = Transaction> {
+    let account_id = AccountId(0);
+    let deposit_id = TransactionId(index);
+    let withdraw_id = TransactionId(index);
+
+    prop_oneof![
+        3 => amount().prop_map(move |amount| Deposit::new(deposit_id.clone(), amount).unwrap().into()),
+        2 => amount().prop_map(move |amount| Withdraw::new(withdraw_id.clone(), amount).unwrap().into()),
+        2 => (0..len).prop_map(|target| Dispute { target_tx_id: TransactionId(target) }.into()),
+        1 => (0..len).prop_map(|target| Resolve { target_tx_id: TransactionId(target) }.into()),
+        1 => (0..len).prop_map(|target| ChargeBack { target_tx_id: TransactionId(target) }.into()),
+    ]
+    .prop_map(move |kind: TransactionKind| Transaction {
+        target_account_id: account_id.clone(),
+        kind,
+    })
+}
+
+fn transactions() -> impl Strategy<Value = Vec<Transaction>> {
+    (1..64u32).prop_flat_map(|len| {
+        (0..len)
+            .map(|index| transaction(index, len))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Bookkeeping of the accepted transactions, independent of the account state.
+struct Accepted {
+    /// deposits - withdrawals - charge backs, at the fixed point scale of the amounts.
+    /// Held funds can push the total above `Amount::MAX`, so it is kept wider.
+    total: u128,
+    deposits: HashMap<TransactionId, Amount>,
+    disputed: HashSet<TransactionId>,
+}
+
+impl Accepted {
+    fn new() -> Self {
+        Accepted {
+            total: 0,
+            deposits: HashMap::new(),
+            disputed: HashSet::new(),
+        }
+    }
+
+    fn record(&mut self, transaction: &Transaction) {
+        match &transaction.kind {
+            TransactionKind::Deposit(deposit) => {
+                self.total += deposit.amount().to_fixed_point();
+                self.deposits
+                    .insert(deposit.to_tx_id(), deposit.to_amount());
+            }
+            TransactionKind::Withdraw(withdraw) => {
+                self.total -= withdraw.amount().to_fixed_point();
+            }
+            TransactionKind::Dispute(dispute) => {
+                self.disputed.insert(dispute.target_tx_id.clone());
+            }
+            TransactionKind::Resolve(resolve) => {
+                self.disputed.remove(&resolve.target_tx_id);
+            }
+            TransactionKind::ChargeBack(charge_back) => {
+                self.disputed.remove(&charge_back.target_tx_id);
+                let amount = &self.deposits[&charge_back.target_tx_id];
+                self.total -= amount.to_fixed_point();
+            }
+        }
+    }
+
+    /// Total of the account, `TotalOverflow` if it is above `Amount::MAX`.
+    fn total(&self) -> Result<Amount, Error> {
+        Amount::from_fixed_point(self.total, Amount::DECIMAL_POINTS)
+            .map_err(|_e| Error::TotalOverflow)
+    }
+
+    /// Sum of the currently disputed deposits.
+    fn held(&self) -> Amount {
+        self.disputed.iter().fold(Amount::MIN, |held, tx_id| {
+            held.checked_add(&self.deposits[tx_id]).unwrap()
+        })
+    }
+}
+
+proptest! {
+    #[test]
+    fn invariants_hold_after_every_transaction(transactions in transactions()) {
+        let mut account = Account::from_id(AccountId(0));
+        let mut accepted = Accepted::new();
+
+        for transaction in transactions {
+            let before = account.state().clone();
+            let result = account.try_apply_transaction(transaction.clone());
+
+            if before.is_locked == IsLocked::Locked {
+                prop_assert_eq!(&result, &Err(Error::LockedAccount));
+            }
+
+            match result {
+                Ok(events) => {
+                    accepted.record(&transaction);
+
+                    // events chain the balances from the previous to the current state
+                    let mut balances = Balances::from(&before);
+                    for event in events {
+                        prop_assert_eq!(&event.before, &balances);
+                        balances = event.after;
+                    }
+                    prop_assert_eq!(&balances, &Balances::from(account.state()));
+                }
+                // failed transactions leave the state untouched
+                Err(_) => prop_assert_eq!(account.state(), &before),
+            }
+
+            prop_assert_eq!(account.state().total(), accepted.total());
+            prop_assert_eq!(&account.state().held, &accepted.held());
+        }
+    }
+
+    #[test]
+    fn events_rebuild_and_balance_the_account(transactions in transactions()) {
+        let mut account = Account::from_id(AccountId(0));
+        let mut journal = Vec::new();
+
+        for transaction in transactions {
+            if let Ok(events) = account.try_apply_transaction(transaction) {
+                journal.extend(events);
+            }
+        }
+
+        let replayed = Account::replay(AccountId(0), journal.clone()).map(Account::into_state);
+        prop_assert_eq!(replayed, Ok(account.state().clone()));
+        prop_assert_eq!(Account::verify(journal.clone(), account.state()), Ok(()));
+
+        let mut ledger = Ledger::default();
+        for event in &journal {
+            prop_assert_eq!(ledger.post(event), Ok(()));
+        }
+        prop_assert_eq!(ledger.trial_balance([account.state()]), Ok(()));
+    }
+}
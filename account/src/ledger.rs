@@ -0,0 +1,162 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    account::{AccountId, AccountState},
+    amount::Amount,
+    error::LedgerError,
+    event::{AccountEvent, AccountEventKind},
+};
+
+/// Account of the ledger. Client funds are owed to the clients, system accounts
+/// hold the funds of the partner.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    /// Available funds of the client.
+    Available(AccountId),
+    /// Held funds of the client.
+    Held(AccountId),
+    /// Funds settled with the payment partner.
+    Settlement,
+}
+
+/// Sums of the amounts debited and credited to a ledger account. Sums are fixed point
+/// numbers at [`Amount::DECIMAL_POINTS`] scale, they may exceed [`Amount::MAX`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub debits: u128,
+    pub credits: u128,
+}
+
+/// Double-entry ledger of the account events. Every event posts entries debiting
+/// and crediting the same amount.
+///
+/// - Deposits debit the settlement and credit the client's available funds.
+/// - Withdrawals debit the client's available funds and credit the settlement.
+/// - Disputes and resolutions move the funds between the client's available and held funds.
+/// - Charge backs debit the client's held funds and credit the settlement, paying the
+///   partner back.
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    accounts: HashMap<LedgerAccount, Totals>,
+}
+
+impl Ledger {
+    /// Post the entries of the event.
+    ///
+    /// # Errors
+    /// Totals of a ledger account overflow. The ledger is not changed.
+    pub fn post(&mut self, event: &AccountEvent) -> Result<(), LedgerError> {
+        let available = || LedgerAccount::Available(event.account_id.clone());
+        let held = || LedgerAccount::Held(event.account_id.clone());
+
+        match &event.kind {
+            AccountEventKind::Deposited { amount } => {
+                self.transfer(LedgerAccount::Settlement, available(), amount)
+            }
+            AccountEventKind::Withdrawn { amount } => {
+                self.transfer(available(), LedgerAccount::Settlement, amount)
+            }
+            AccountEventKind::FundsHeld { amount } => self.transfer(available(), held(), amount),
+            AccountEventKind::FundsReleased { amount } => {
+                self.transfer(held(), available(), amount)
+            }
+            AccountEventKind::ChargedBack { amount } => {
+                self.transfer(held(), LedgerAccount::Settlement, amount)
+            }
+            AccountEventKind::AccountLocked => Ok(()),
+        }
+    }
+
+    /// Totals of the ledger account. Zero if nothing was posted to it.
+    pub fn totals(&self, account: &LedgerAccount) -> Totals {
+        self.accounts.get(account).cloned().unwrap_or_default()
+    }
+
+    /// Check that the funds settled with the partner match the sum of the account
+    /// balances, and that the client balances of the ledger match the account states.
+    ///
+    /// # Errors
+    /// The ledger diverges from the account states.
+    pub fn trial_balance<'a>(
+        &self,
+        states: impl IntoIterator<Item = &'a AccountState>,
+    ) -> Result<(), LedgerError> {
+        let states = states.into_iter().collect::<Vec<_>>();
+
+        let mut balances = 0u128;
+        for state in &states {
+            balances = balances
+                .checked_add(state.available.to_fixed_point())
+                .and_then(|sum| sum.checked_add(state.held.to_fixed_point()))
+                .ok_or(LedgerError::Overflow)?;
+        }
+
+        // the settlement is debited with the funds owed to the clients
+        let settlement = self.totals(&LedgerAccount::Settlement);
+        if settlement.credits.checked_add(balances) != Some(settlement.debits) {
+            return Err(LedgerError::SettlementMismatch {
+                settlement,
+                balances,
+            });
+        }
+
+        // clients of the ledger without an account state must not hold any funds
+        let mut expected = HashMap::new();
+        for state in states {
+            expected.insert(LedgerAccount::Available(state.id.clone()), &state.available);
+            expected.insert(LedgerAccount::Held(state.id.clone()), &state.held);
+        }
+        let clients = self
+            .accounts
+            .keys()
+            .filter(|account| {
+                matches!(
+                    account,
+                    LedgerAccount::Available(_) | LedgerAccount::Held(_)
+                )
+            })
+            .chain(expected.keys())
+            .collect::<BTreeSet<_>>();
+
+        for account in clients {
+            let totals = self.totals(account);
+            let balance = expected.get(account).copied().unwrap_or(&Amount::MIN);
+
+            // client accounts are credited with the funds owed to the client
+            if totals.debits.checked_add(balance.to_fixed_point()) != Some(totals.credits) {
+                return Err(LedgerError::BalanceMismatch {
+                    account: account.clone(),
+                    totals,
+                    balance: balance.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Debit and credit the amount. The ledger is not changed if a total overflows.
+    fn transfer(
+        &mut self,
+        debit: LedgerAccount,
+        credit: LedgerAccount,
+        amount: &Amount,
+    ) -> Result<(), LedgerError> {
+        let amount = amount.to_fixed_point();
+        let debits = self
+            .totals(&debit)
+            .debits
+            .checked_add(amount)
+            .ok_or(LedgerError::Overflow)?;
+        let credits = self
+            .totals(&credit)
+            .credits
+            .checked_add(amount)
+            .ok_or(LedgerError::Overflow)?;
+
+        self.accounts.entry(debit).or_default().debits = debits;
+        self.accounts.entry(credit).or_default().credits = credits;
+
+        Ok(())
+    }
+}
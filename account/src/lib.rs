@@ -1,15 +1,26 @@
 mod account;
 mod amount;
 mod error;
+mod event;
+mod ledger;
+mod replay;
 mod transaction;
 
+#[cfg(test)]
+mod invariant_tests;
+
 // TODO: cleanup imports
 // TODO: what does the account symbol clash with
+#[cfg(feature = "serde")]
+pub use crate::account::account_state_record::AccountStateRecord;
 pub use crate::account::{Account, AccountId, AccountState, IsLocked};
 pub use amount::Amount;
-pub use error::Error;
+pub use error::{Error, LedgerError, ReplayError};
+pub use event::{AccountEvent, AccountEventKind, AccountEvents, Balances};
+pub use ledger::{Ledger, LedgerAccount, Totals};
 pub use transaction::{
-    ChargeBack, Deposit, Dispute, Resolve, Transaction, TransactionId, TransactionKind, Withdraw,
+    ChargeBack, Deposit, Dispute, Resolve, TimedTransaction, Transaction, TransactionId,
+    TransactionKind, Withdraw,
 };
 
 // TODO: move into test utils
@@ -129,6 +140,35 @@ mod tests {
         err
     }
 
+    #[test_case(15, 1 => Ok(Amount::from_fixed_point(15_000, 4).unwrap()) ; "Fixed point amount")]
+    #[test_case(100005, 5 => Ok(Amount::from_u64(1)) ; "Fixed point amount rounded to nearest even")]
+    #[test_case(u128::MAX, 4 => Err(Error::AmountOutOfBounds) ; "Fixed point mantissa over 96 bits")]
+    #[test_case(1, 29 => Err(Error::AmountOutOfBounds) ; "Fixed point scale over max")]
+    fn amount_from_fixed_point(mantissa: u128, scale: u32) -> Result<Amount, Error> {
+        Amount::from_fixed_point(mantissa, scale)
+    }
+
+    #[test_case(Amount::from_u64(15) => 150_000 ; "Integer amount")]
+    #[test_case(Amount::from_fixed_point(15, 1).unwrap() => 15_000 ; "Fractional amount")]
+    #[test_case(Amount::MAX => (1u128 << 96) - 1 ; "Max amount")]
+    fn amount_to_fixed_point(amount: Amount) -> u128 {
+        amount.to_fixed_point()
+    }
+
+    #[test_case(Amount::from_u64(15) => "15.0000" ; "Integer amount")]
+    #[test_case(Amount::from_fixed_point(15, 1).unwrap() => "1.5000" ; "Fractional amount")]
+    #[test_case(Amount::MAX => "7922816251426433759354395.0335" ; "Max amount")]
+    fn amount_to_fixed_string(amount: Amount) -> String {
+        amount.to_fixed_string()
+    }
+
+    #[test_case(0 => "0.0000" ; "Zero")]
+    #[test_case(15_000 => "1.5000" ; "Fractional sum")]
+    #[test_case(Amount::MAX.to_fixed_point() * 2 => "15845632502852867518708790.0670" ; "Sum over max amount")]
+    fn fixed_point_to_string(mantissa: u128) -> String {
+        Amount::fixed_point_to_string(mantissa)
+    }
+
     #[test]
     fn create_empty_account() {
         let target_account_id = AccountId(0);
@@ -406,4 +446,217 @@ mod tests {
     fn charge_back_success(account: Account, tx: Transaction, expected_state: AccountState) {
         test_success(account, tx, expected_state)
     }
+
+    #[test_case(
+        acc!(0, []),
+        Transaction::deposit(AccountId(0), TransactionId(0), Amount::from_u64(10)).unwrap()
+        => vec![AccountEventKind::Deposited { amount: Amount::from_u64(10) }]
+    ; "Deposit")]
+    #[test_case(
+        acc!(0, [Deposit(15)]),
+        Transaction::withdraw(AccountId(0), TransactionId(1), Amount::from_u64(10)).unwrap()
+        => vec![AccountEventKind::Withdrawn { amount: Amount::from_u64(10) }]
+    ; "Withdraw")]
+    #[test_case(
+        acc!(0, [Deposit(15)]),
+        Transaction::dispute(AccountId(0), TransactionId(0))
+        => vec![AccountEventKind::FundsHeld { amount: Amount::from_u64(15) }]
+    ; "Dispute")]
+    #[test_case(
+        acc!(0, [Deposit(15), Dispute(0)]),
+        Transaction::resolve(AccountId(0), TransactionId(0))
+        => vec![AccountEventKind::FundsReleased { amount: Amount::from_u64(15) }]
+    ; "Resolve")]
+    #[test_case(
+        acc!(0, [Deposit(15), Dispute(0)]),
+        Transaction::charge_back(AccountId(0), TransactionId(0))
+        => vec![
+            AccountEventKind::ChargedBack { amount: Amount::from_u64(15) },
+            AccountEventKind::AccountLocked,
+        ]
+    ; "Charge back locks the account")]
+    fn transaction_events(mut account: Account, tx: Transaction) -> Vec<AccountEventKind> {
+        let events = account.try_apply_transaction(tx).expect("testing success");
+
+        events.into_iter().map(|event| event.kind).collect()
+    }
+
+    #[test]
+    fn events_record_the_balances() {
+        let mut account = Account::from_id(AccountId(0));
+        let deposit = Transaction::deposit(AccountId(0), TransactionId(0), Amount::from_u64(15));
+        account.try_apply_transaction(deposit.unwrap()).unwrap();
+
+        let events = account
+            .try_apply_transaction(Transaction::dispute(AccountId(0), TransactionId(0)))
+            .unwrap();
+
+        assert_eq!(
+            events.into_iter().collect::<Vec<_>>(),
+            [AccountEvent {
+                account_id: AccountId(0),
+                tx_id: TransactionId(0),
+                kind: AccountEventKind::FundsHeld {
+                    amount: Amount::from_u64(15)
+                },
+                before: Balances {
+                    available: Amount::from_u64(15),
+                    held: Amount::from_u64(0),
+                },
+                after: Balances {
+                    available: Amount::from_u64(0),
+                    held: Amount::from_u64(15),
+                },
+            }]
+        );
+    }
+
+    /// Events of depositing 15, disputing and charging back the deposit.
+    fn charged_back_journal() -> Vec<AccountEvent> {
+        let mut account = Account::from_id(AccountId(0));
+        [
+            Transaction::deposit(AccountId(0), TransactionId(0), Amount::from_u64(15)).unwrap(),
+            Transaction::dispute(AccountId(0), TransactionId(0)),
+            Transaction::charge_back(AccountId(0), TransactionId(0)),
+        ]
+        .into_iter()
+        .flat_map(|tx| account.try_apply_transaction(tx).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn replay_rebuilds_the_account() {
+        let journal = charged_back_journal();
+
+        let account = Account::replay(AccountId(0), journal).unwrap();
+
+        assert_eq!(
+            account.into_state(),
+            AccountState::new(AccountId(0), 0u64, 0u64, IsLocked::Locked)
+        );
+    }
+
+    #[test_case(
+        |journal| journal[0].kind = AccountEventKind::Deposited { amount: Amount::from_u64(20) }
+        => matches Err(ReplayError::Mismatch { position: 0, .. })
+    ; "Tampered deposit amount")]
+    #[test_case(
+        |journal| journal[1].after.held = Amount::from_u64(10)
+        => matches Err(ReplayError::Mismatch { position: 1, .. })
+    ; "Tampered balances")]
+    #[test_case(
+        |journal| journal[0].account_id = AccountId(1)
+        => matches Err(ReplayError::Mismatch { position: 0, .. })
+    ; "Event of another account")]
+    #[test_case(
+        |journal| { journal.remove(1); }
+        => Err(ReplayError::Rejected { position: 1, error: Error::TargetNotDisputed })
+    ; "Missing dispute")]
+    #[test_case(
+        |journal| { journal.pop(); }
+        => Err(ReplayError::Truncated)
+    ; "Missing account lock")]
+    #[test_case(
+        |journal| { journal.remove(2); }
+        => Err(ReplayError::UnexpectedEvent { position: 2 })
+    ; "Account lock without charge back")]
+    #[test_case(
+        |journal| journal.insert(1, journal[0].clone())
+        => Err(ReplayError::Rejected { position: 1, error: Error::TransactionReplay })
+    ; "Duplicated deposit")]
+    #[test_case(
+        |journal| {
+            let mut deposit = journal[0].clone();
+            deposit.before = journal[1].after.clone();
+            deposit.after.held = deposit.before.held.clone();
+            journal.insert(2, deposit);
+        }
+        => Err(ReplayError::Rejected { position: 2, error: Error::TransactionReplay })
+    ; "Duplicated deposit of a disputed transaction")]
+    fn replay_detects_tampering(tamper: fn(&mut Vec<AccountEvent>)) -> Result<(), ReplayError> {
+        let mut journal = charged_back_journal();
+        tamper(&mut journal);
+
+        Account::replay(AccountId(0), journal).map(|_account| ())
+    }
+
+    #[test]
+    fn verify_compares_the_stored_state() {
+        let stored = AccountState::new(AccountId(0), 0u64, 0u64, IsLocked::Locked);
+        let tampered = AccountState::new(AccountId(0), 15u64, 0u64, IsLocked::Locked);
+
+        assert_eq!(Account::verify(charged_back_journal(), &stored), Ok(()));
+        assert_eq!(
+            Account::verify(charged_back_journal(), &tampered),
+            Err(ReplayError::StateMismatch {
+                replayed: Box::new(stored)
+            })
+        );
+    }
+
+    #[test]
+    fn ledger_posts_balanced_entries() {
+        let mut ledger = Ledger::default();
+        for event in charged_back_journal() {
+            ledger.post(&event).unwrap();
+        }
+
+        let state = AccountState::new(AccountId(0), 0u64, 0u64, IsLocked::Locked);
+        assert_eq!(ledger.trial_balance([&state]), Ok(()));
+
+        // the charged back deposit was paid back to the partner from the client's held funds
+        let settled = Amount::from_u64(15).to_fixed_point();
+        let totals = Totals {
+            debits: settled,
+            credits: settled,
+        };
+        assert_eq!(ledger.totals(&LedgerAccount::Settlement), totals);
+        assert_eq!(ledger.totals(&LedgerAccount::Held(AccountId(0))), totals);
+    }
+
+    #[test_case(
+        3,
+        AccountState::new(AccountId(0), 15u64, 0u64, IsLocked::Locked)
+        => Err(LedgerError::SettlementMismatch {
+            settlement: Totals { debits: 150_000, credits: 150_000 },
+            balances: 150_000,
+        })
+    ; "Settled funds diverge")]
+    #[test_case(
+        3,
+        AccountState::new(AccountId(1), 5u64, 0u64, IsLocked::Unlocked)
+        => Err(LedgerError::SettlementMismatch {
+            settlement: Totals { debits: 150_000, credits: 150_000 },
+            balances: 50_000,
+        })
+    ; "Account missing from the ledger")]
+    #[test_case(
+        2,
+        AccountState::new(AccountId(0), 15u64, 0u64, IsLocked::Unlocked)
+        => Err(LedgerError::BalanceMismatch {
+            account: LedgerAccount::Available(AccountId(0)),
+            totals: Totals { debits: 150_000, credits: 150_000 },
+            balance: Amount::from_u64(15),
+        })
+    ; "Available funds diverge")]
+    #[test_case(
+        2,
+        AccountState::new(AccountId(1), 0u64, 15u64, IsLocked::Unlocked)
+        => Err(LedgerError::BalanceMismatch {
+            account: LedgerAccount::Held(AccountId(0)),
+            totals: Totals { debits: 0, credits: 150_000 },
+            balance: Amount::MIN,
+        })
+    ; "Account missing from the states")]
+    fn trial_balance_detects_divergence(
+        events: usize,
+        state: AccountState,
+    ) -> Result<(), LedgerError> {
+        let mut ledger = Ledger::default();
+        for event in charged_back_journal().iter().take(events) {
+            ledger.post(event).unwrap();
+        }
+
+        ledger.trial_balance([&state])
+    }
 }
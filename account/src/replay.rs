@@ -0,0 +1,113 @@
+use crate::{
+    account::{Account, AccountId, AccountState},
+    error::{Error, ReplayError},
+    event::{AccountEvent, AccountEventKind},
+    transaction::{Transaction, TransactionKind},
+};
+
+impl Account {
+    /// Rebuild the account from the events of its applied transactions, given in the order
+    /// they happened.
+    ///
+    /// Every event is replayed as the transaction that caused it. The replayed events,
+    /// including their balances, must match the journal.
+    ///
+    /// # Errors
+    /// The journal does not describe a valid history of the account. A deposit repeated
+    /// in the journal is rejected with [`Error::TransactionReplay`].
+    pub fn replay(
+        account_id: AccountId,
+        events: impl IntoIterator<Item = AccountEvent>,
+    ) -> Result<Self, ReplayError> {
+        let mut account = Account::from_id(account_id);
+        let mut journal = events.into_iter().enumerate();
+
+        while let Some((position, event)) = journal.next() {
+            let rejected = |error| ReplayError::Rejected { position, error };
+
+            let transaction = replayed_transaction(&account.state().id, &event)
+                .ok_or(ReplayError::UnexpectedEvent { position })?
+                .map_err(rejected)?;
+            // accounts assume unique deposit ids, a journal is not trusted to have them
+            if let TransactionKind::Deposit(deposit) = &transaction.kind {
+                if account.has_deposit(deposit.tx_id()) {
+                    return Err(rejected(Error::TransactionReplay));
+                }
+            }
+            let mut replayed = account
+                .try_apply_transaction(transaction)
+                .map_err(rejected)?
+                .into_iter();
+
+            // the first event is caused by the transaction, the rest follow it
+            let first = replayed
+                .next()
+                .expect("Applied transactions must have an event");
+            compare(position, &event, first)?;
+            for replayed in replayed {
+                let (position, event) = journal.next().ok_or(ReplayError::Truncated)?;
+                compare(position, &event, replayed)?;
+            }
+        }
+
+        Ok(account)
+    }
+
+    /// Replay the journal and compare the result with the stored account state.
+    ///
+    /// # Errors
+    /// The journal is invalid, or does not lead to the stored state.
+    pub fn verify(
+        events: impl IntoIterator<Item = AccountEvent>,
+        stored: &AccountState,
+    ) -> Result<(), ReplayError> {
+        let account = Account::replay(stored.id.clone(), events)?;
+
+        if account.state() == stored {
+            Ok(())
+        } else {
+            Err(ReplayError::StateMismatch {
+                replayed: Box::new(account.into_state()),
+            })
+        }
+    }
+}
+
+/// Transaction causing the event. `None` for events following the event of a transaction.
+fn replayed_transaction(
+    account_id: &AccountId,
+    event: &AccountEvent,
+) -> Option<Result<Transaction, Error>> {
+    let account_id = account_id.clone();
+    let tx_id = event.tx_id.clone();
+
+    let transaction = match &event.kind {
+        AccountEventKind::Deposited { amount } => {
+            Transaction::deposit(account_id, tx_id, amount.clone())
+        }
+        AccountEventKind::Withdrawn { amount } => {
+            Transaction::withdraw(account_id, tx_id, amount.clone())
+        }
+        AccountEventKind::FundsHeld { .. } => Ok(Transaction::dispute(account_id, tx_id)),
+        AccountEventKind::FundsReleased { .. } => Ok(Transaction::resolve(account_id, tx_id)),
+        AccountEventKind::ChargedBack { .. } => Ok(Transaction::charge_back(account_id, tx_id)),
+        AccountEventKind::AccountLocked => return None,
+    };
+
+    Some(transaction)
+}
+
+fn compare(
+    position: usize,
+    event: &AccountEvent,
+    replayed: AccountEvent,
+) -> Result<(), ReplayError> {
+    if event == &replayed {
+        Ok(())
+    } else {
+        Err(ReplayError::Mismatch {
+            position,
+            replayed: Box::new(replayed),
+        })
+    }
+}
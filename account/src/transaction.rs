@@ -21,7 +21,7 @@ pub struct TransactionId(pub u32);
     derive(Serialize, Deserialize),
     serde(
         into = "transaction_record::TransactionRecord",
-        from = "transaction_record::TransactionRecord"
+        try_from = "transaction_record::TransactionRecord"
     )
 )]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -76,6 +76,24 @@ impl Transaction {
     }
 }
 
+/// Transaction with the timestamp of its record, if the record has one.
+///
+/// Timestamps are integers, such as unix epoch milliseconds. Their unit is not checked,
+/// feeds that are compared must share it.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(
+        into = "transaction_record::TransactionRecord",
+        try_from = "transaction_record::TransactionRecord"
+    )
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedTransaction {
+    pub timestamp: Option<u64>,
+    pub transaction: Transaction,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, From)]
 pub enum TransactionKind {
     Deposit(Deposit),
@@ -85,6 +103,26 @@ pub enum TransactionKind {
     ChargeBack(ChargeBack),
 }
 
+impl TransactionKind {
+    /// Type, transaction id and amount of the transaction. Disputes, resolutions and
+    /// charge backs refer to the disputed deposit, and carry no amount.
+    pub fn fields(&self) -> (&'static str, &TransactionId, Option<&Amount>) {
+        match self {
+            TransactionKind::Deposit(deposit) => {
+                ("deposit", deposit.tx_id(), Some(deposit.amount()))
+            }
+            TransactionKind::Withdraw(withdraw) => {
+                ("withdraw", withdraw.tx_id(), Some(withdraw.amount()))
+            }
+            TransactionKind::Dispute(dispute) => ("dispute", &dispute.target_tx_id, None),
+            TransactionKind::Resolve(resolve) => ("resolve", &resolve.target_tx_id, None),
+            TransactionKind::ChargeBack(charge_back) => {
+                ("chargeback", &charge_back.target_tx_id, None)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Deposit {
     tx_id: TransactionId,
@@ -174,7 +212,9 @@ pub struct ChargeBack {
 mod transaction_record {
     use serde::{Deserialize, Serialize};
 
-    use crate::{AccountId, Amount, Transaction, TransactionId, TransactionKind};
+    use crate::{
+        AccountId, Amount, Error, TimedTransaction, Transaction, TransactionId, TransactionKind,
+    };
 
     #[derive(Serialize, Deserialize)]
     pub struct TransactionRecord<'a> {
@@ -182,6 +222,9 @@ mod transaction_record {
         client: AccountId,
         tx: TransactionId,
         amount: Option<Amount>,
+        /// Optional column, ignored by the readers of plain transactions.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timestamp: Option<u64>,
     }
 
     impl<'a> From<Transaction> for TransactionRecord<'a> {
@@ -225,26 +268,51 @@ mod transaction_record {
                 client,
                 tx,
                 amount,
+                timestamp: None,
+            }
+        }
+    }
+
+    impl<'a> From<TimedTransaction> for TransactionRecord<'a> {
+        fn from(timed: TimedTransaction) -> Self {
+            TransactionRecord {
+                timestamp: timed.timestamp,
+                ..timed.transaction.into()
             }
         }
     }
 
-    impl<'a> From<TransactionRecord<'a>> for Transaction {
-        fn from(tx_record: TransactionRecord) -> Self {
+    impl<'a> TryFrom<TransactionRecord<'a>> for TimedTransaction {
+        type Error = Error;
+
+        fn try_from(tx_record: TransactionRecord) -> Result<Self, Self::Error> {
+            Ok(TimedTransaction {
+                timestamp: tx_record.timestamp,
+                transaction: tx_record.try_into()?,
+            })
+        }
+    }
+
+    impl<'a> TryFrom<TransactionRecord<'a>> for Transaction {
+        type Error = Error;
+
+        fn try_from(tx_record: TransactionRecord) -> Result<Self, Self::Error> {
             match tx_record.r#type {
-                "deposit" => {
-                    Transaction::deposit(tx_record.client, tx_record.tx, tx_record.amount.unwrap())
-                        .unwrap()
-                }
-                "withdraw" => {
-                    Transaction::withdraw(tx_record.client, tx_record.tx, tx_record.amount.unwrap())
-                        .unwrap()
-                }
+                "deposit" => Transaction::deposit(
+                    tx_record.client,
+                    tx_record.tx,
+                    tx_record.amount.ok_or(Error::MissingAmount)?,
+                ),
+                "withdraw" => Transaction::withdraw(
+                    tx_record.client,
+                    tx_record.tx,
+                    tx_record.amount.ok_or(Error::MissingAmount)?,
+                ),
                 // TODO: assert that amount is none?
-                "resolve" => Transaction::resolve(tx_record.client, tx_record.tx),
-                "dispute" => Transaction::dispute(tx_record.client, tx_record.tx),
-                "chargeback" => Transaction::charge_back(tx_record.client, tx_record.tx),
-                _ => panic!(),
+                "resolve" => Ok(Transaction::resolve(tx_record.client, tx_record.tx)),
+                "dispute" => Ok(Transaction::dispute(tx_record.client, tx_record.tx)),
+                "chargeback" => Ok(Transaction::charge_back(tx_record.client, tx_record.tx)),
+                _ => Err(Error::UnknownTransactionType),
             }
         }
     }
@@ -1,2 +1,336 @@
+use std::{
+    env,
+    io::{self, BufWriter, IsTerminal},
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    pin::pin,
+    time::Duration,
+};
+
+use account::{Ledger, LedgerError, Transaction};
+
+use async_std::{fs::File, task};
+use clap::{Args, Parser, Subcommand};
+use futures::{
+    future, stream, stream::BoxStream, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Stream,
+    StreamExt, TryStreamExt,
+};
+use transaction_broker::{
+    deltas_into_writer, expand_paths, txs_from_sources, txs_merged_by_timestamp, ActorBroker,
+    Broker, Compression, Emission, InputFormat, OutOfOrderRecords, OutputFormat, SequencedEvent,
+    SourceFile, Sources, StreamingBroker, Table,
+};
+
+use crate::report::{Report, ReportFormat};
+use crate::statement::{export_statements, StatementArgs};
+
+mod report;
+mod statement;
+
+/// Period between two reads of a followed input that has no new data.
+const FOLLOW_POLL_PERIOD: Duration = Duration::from_millis(100);
+
+/// Applies the csv or json lines transactions and writes the account states to stdout.
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    Statement(StatementArgs),
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
+    /// Transactions files, directories, glob patterns or named pipes, applied in the given
+    /// order as one stream. Directories are read in file name order. Stdin if omitted or `-`.
+    inputs: Vec<PathBuf>,
+    /// Format of the transactions, csv or jsonl. Detected from the extension of every input
+    /// file if omitted, `.jsonl` and `.ndjson` files are json lines.
+    #[arg(long, value_name = "FORMAT")]
+    input_format: Option<InputFormat>,
+    /// Compression of the transactions, none, gzip or zstd. Detected from the extension of
+    /// every input file if omitted, `.gz` files are gzip and `.zst` files are zstd.
+    #[arg(long, value_name = "COMPRESSION")]
+    input_compression: Option<Compression>,
+    /// Compression of the output, none, gzip or zstd. The checksum of the report is
+    /// computed before compression.
+    #[arg(long, value_name = "COMPRESSION", default_value_t)]
+    output_compression: Compression,
+    /// Run until the input ends, writing the changed accounts as they change,
+    /// prefixed with a sequence number.
+    #[arg(long)]
+    stream: bool,
+    /// Write the changed accounts after every given number of transactions.
+    #[arg(long, value_name = "N", requires = "stream")]
+    every_transactions: Option<NonZeroU64>,
+    /// Write the changed accounts after every given number of seconds, greater than 0.
+    #[arg(long, value_name = "T", requires = "stream", value_parser = parse_seconds)]
+    every_seconds: Option<Duration>,
+    /// Keep reading the input file as it grows, instead of stopping at its end.
+    /// Takes a single input file.
+    #[arg(long, requires = "stream", requires = "inputs")]
+    follow: bool,
+    /// Merge the inputs by the `timestamp` column of their records, instead of applying them
+    /// one after the other. Every input is a feed, the files of a directory or pattern are
+    /// read one after the other. Records out of order within their feed are reported to stderr.
+    #[arg(long, requires = "inputs")]
+    merge: bool,
+    /// Keep a double-entry ledger of the account events. Fails without writing the accounts
+    /// if the ledger does not balance against them.
+    #[arg(long, conflicts_with = "stream")]
+    ledger: bool,
+    /// Write a reconciliation report of the run into the given file.
+    #[arg(long, value_name = "PATH", conflicts_with = "stream")]
+    report: Option<PathBuf>,
+    /// Format of the reconciliation report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text, requires = "report")]
+    report_format: ReportFormat,
+    /// Format of the account states, csv, json, jsonl, table, or parquet with the `parquet`
+    /// feature. Json amounts are strings with 4 decimals. Tables written to a terminal
+    /// color the locked accounts, unless `NO_COLOR` is set.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value_t,
+        conflicts_with = "stream"
+    )]
+    output_format: OutputFormat,
+    /// Write a table of the rejected transactions into the given file. With the `parquet`
+    /// feature, files with a `parquet` extension are written as parquet.
+    #[arg(long, value_name = "PATH", conflicts_with = "stream")]
+    rejections: Option<PathBuf>,
+    /// End the tables with a row of the column totals.
+    #[arg(long)]
+    totals: bool,
+}
+
 #[async_std::main]
-async fn main() {}
+async fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Some(Command::Statement(args)) => export_statements(args),
+        None => run(&cli.run).await,
+    }
+}
+
+async fn run(args: &RunArgs) -> io::Result<()> {
+    let feeds = open(args).await?;
+    let (transactions, sources, out_of_order) = if args.merge {
+        txs_merged_by_timestamp(feeds)
+    } else {
+        let (transactions, sources) = txs_from_sources(feeds.into_iter().flatten().collect());
+        (transactions, sources, OutOfOrderRecords::default())
+    };
+
+    let mut output = args.output_compression.encoder(async_std::io::stdout());
+    process(args, transactions, &sources, &mut output).await?;
+
+    for record in out_of_order.records() {
+        eprintln!(
+            "{}:{}: timestamp {} is before the latest timestamp {} of its feed",
+            record.source.path.display(),
+            record.source.line,
+            record.timestamp,
+            record.latest
+        );
+    }
+
+    // compressed streams end when closed
+    output.close().await
+}
+
+/// Process the transactions and write the account states, or the deltas when streaming,
+/// into the output.
+async fn process(
+    args: &RunArgs,
+    transactions: BoxStream<'static, Transaction>,
+    sources: &Sources,
+    mut output: impl AsyncWrite + Unpin,
+) -> io::Result<()> {
+    if args.stream {
+        let broker = StreamingBroker {
+            emission: Emission {
+                transactions: args.every_transactions,
+                interval: args.every_seconds,
+            },
+            ..StreamingBroker::default()
+        };
+
+        deltas_into_writer(output, Box::pin(broker.deltas(transactions))).await
+    } else {
+        let broker = ActorBroker::default();
+        let broker_output = if args.ledger {
+            let (events, broker_output) = broker.process_with_events(Box::pin(transactions));
+            let (ledger, broker_output) =
+                future::join(ledger_of_events(events), broker_output).await;
+
+            ledger
+                .and_then(|ledger| ledger.trial_balance(&broker_output.accounts))
+                .map_err(io::Error::other)?;
+            broker_output
+        } else {
+            broker.process(Box::pin(transactions)).await
+        };
+
+        if let Some(rejections_path) = &args.rejections {
+            let table = Table {
+                totals: args.totals,
+                color: false,
+            };
+            let mut file = BufWriter::new(std::fs::File::create(rejections_path)?);
+            #[cfg(feature = "parquet")]
+            if rejections_path
+                .extension()
+                .is_some_and(|extension| extension == "parquet")
+            {
+                transaction_broker::rejections_into_parquet(&mut file, &broker_output.rejections)
+                    .map_err(io::Error::other)?;
+            } else {
+                table.rejections_with_sources_into_writer(
+                    &mut file,
+                    &broker_output.rejections,
+                    sources,
+                )?;
+            }
+            #[cfg(not(feature = "parquet"))]
+            table.rejections_with_sources_into_writer(
+                &mut file,
+                &broker_output.rejections,
+                sources,
+            )?;
+        }
+
+        let output_format = match args.output_format {
+            OutputFormat::Table(_) => OutputFormat::Table(Table {
+                totals: args.totals,
+                color: io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            }),
+            output_format => output_format,
+        };
+
+        let Some(report_path) = &args.report else {
+            return output_format
+                .accounts_into_writer(output, stream::iter(broker_output.accounts))
+                .await;
+        };
+
+        // the report holds the checksum of the written accounts
+        let mut accounts = Vec::new();
+        output_format
+            .accounts_into_writer(&mut accounts, stream::iter(broker_output.accounts.clone()))
+            .await?;
+        Report::new(&broker_output, &accounts).write(report_path, args.report_format)?;
+
+        output.write_all(&accounts).await?;
+        output.flush().await
+    }
+}
+
+/// Parse a period given in seconds. The period must be at least a nanosecond.
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    let period = seconds
+        .parse::<f64>()
+        .map_err(|err| err.to_string())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).map_err(|err| err.to_string()))?;
+
+    if period.is_zero() {
+        return Err("period must be greater than 0".to_owned());
+    }
+    Ok(period)
+}
+
+/// Format of the input, given by the flag or detected from the input file extension.
+fn input_format(input: Option<&Path>, flag: Option<InputFormat>) -> InputFormat {
+    flag.unwrap_or_else(|| input.map_or_else(InputFormat::default, InputFormat::from_path))
+}
+
+/// Post the events to a new ledger.
+async fn ledger_of_events(
+    events: impl Stream<Item = SequencedEvent>,
+) -> Result<Ledger, LedgerError> {
+    let mut events = pin!(events);
+    let mut ledger = Ledger::default();
+
+    while let Some(event) = events.next().await {
+        ledger.post(&event.event)?;
+    }
+
+    Ok(ledger)
+}
+
+/// Open the inputs, in the given order. Returns a feed for every input, with the
+/// transactions of the files it expands to.
+async fn open(args: &RunArgs) -> io::Result<Vec<Vec<SourceFile>>> {
+    let feeds = match args.inputs.as_slice() {
+        [] => vec![vec![PathBuf::from("-")]],
+        inputs => inputs
+            .iter()
+            .map(|input| expand_paths(&[input]))
+            .collect::<io::Result<_>>()?,
+    };
+    if args.follow && feeds.iter().map(Vec::len).sum::<usize>() > 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Only a single input file can be followed",
+        ));
+    }
+
+    let mut inputs = Vec::with_capacity(feeds.len());
+    for paths in feeds {
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let input = open_path(args, &path).await?;
+            let transactions =
+                input_format(Some(&path), args.input_format).txs_with_lines_from_reader(input);
+            files.push((path, transactions));
+        }
+        inputs.push(files);
+    }
+
+    Ok(inputs)
+}
+
+/// Open the input file, or stdin for `-`, decompressed as it is read.
+async fn open_path(args: &RunArgs, path: &Path) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+    let compression = args
+        .input_compression
+        .unwrap_or_else(|| Compression::from_path(path));
+    if path.as_os_str() == "-" {
+        return Ok(compression.decoder(async_std::io::stdin()));
+    }
+
+    let file = File::open(path).await?;
+    if args.follow {
+        Ok(compression.decoder(follow(file)))
+    } else {
+        Ok(compression.decoder(file))
+    }
+}
+
+/// Reads the file as it grows. The end of the file is never reached, the input ends
+/// after the first read error.
+fn follow(file: File) -> impl AsyncRead + Unpin + Send {
+    let chunks = stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match file.read(&mut buf).await {
+                Ok(0) => task::sleep(FOLLOW_POLL_PERIOD).await,
+                Ok(len) => {
+                    buf.truncate(len);
+                    return Some((Ok(buf), Some(file)));
+                }
+                Err(err) => return Some((Err(err), None)),
+            }
+        }
+    });
+
+    Box::pin(chunks).into_async_read()
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use account::{Amount, IsLocked};
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use transaction_broker::{BrokerOutput, Tally, TypeTally};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// End of run reconciliation of the processed transactions against the written accounts.
+#[derive(Serialize)]
+pub struct Report {
+    /// Applied and rejected transactions, by transaction type.
+    transactions: BTreeMap<&'static str, TypeRecord>,
+    /// Rejected transactions, by the name of the error that rejected them.
+    rejections: BTreeMap<String, TallyRecord>,
+    held: String,
+    locked_accounts: usize,
+    /// Hex encoded SHA-256 of the written accounts.
+    checksum: String,
+}
+
+#[derive(Serialize)]
+struct TypeRecord {
+    applied: TallyRecord,
+    rejected: TallyRecord,
+}
+
+#[derive(Serialize)]
+struct TallyRecord {
+    count: u64,
+    amount: String,
+}
+
+impl From<TypeTally> for TypeRecord {
+    fn from(tally: TypeTally) -> Self {
+        TypeRecord {
+            applied: tally.applied.into(),
+            rejected: tally.rejected.into(),
+        }
+    }
+}
+
+impl From<Tally> for TallyRecord {
+    fn from(tally: Tally) -> Self {
+        TallyRecord {
+            count: tally.count,
+            amount: Amount::fixed_point_to_string(tally.amount),
+        }
+    }
+}
+
+impl Report {
+    /// Report of the broker output, written as `accounts`.
+    pub fn new(output: &BrokerOutput, accounts: &[u8]) -> Self {
+        let tallies = &output.tallies;
+        let transactions = BTreeMap::from([
+            ("deposit", tallies.deposits.into()),
+            ("withdraw", tallies.withdrawals.into()),
+            ("dispute", tallies.disputes.into()),
+            ("resolve", tallies.resolves.into()),
+            ("chargeback", tallies.chargebacks.into()),
+        ]);
+        let rejections = tallies
+            .errors
+            .iter()
+            .map(|(error, tally)| (format!("{error:?}"), (*tally).into()))
+            .collect();
+
+        Report {
+            transactions,
+            rejections,
+            held: Amount::fixed_point_to_string(
+                output
+                    .accounts
+                    .iter()
+                    .map(|state| state.held.to_fixed_point())
+                    .sum(),
+            ),
+            locked_accounts: output
+                .accounts
+                .iter()
+                .filter(|state| state.is_locked == IsLocked::Locked)
+                .count(),
+            checksum: Sha256::digest(accounts)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        }
+    }
+
+    /// Write the report into the file, truncating it.
+    pub fn write(&self, path: &Path, format: ReportFormat) -> io::Result<()> {
+        let mut file = io::BufWriter::new(File::create(path)?);
+
+        match format {
+            ReportFormat::Text => self.write_text(&mut file)?,
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut file, self)?;
+                writeln!(file)?;
+            }
+        }
+
+        file.flush()
+    }
+
+    fn write_text(&self, mut writer: impl Write) -> io::Result<()> {
+        for (kind, record) in &self.transactions {
+            writeln!(
+                writer,
+                "{kind}: {} applied ({}), {} rejected ({})",
+                record.applied.count,
+                record.applied.amount,
+                record.rejected.count,
+                record.rejected.amount
+            )?;
+        }
+        for (error, record) in &self.rejections {
+            writeln!(
+                writer,
+                "rejected by {error}: {} ({})",
+                record.count, record.amount
+            )?;
+        }
+        writeln!(writer, "held: {}", self.held)?;
+        writeln!(writer, "locked accounts: {}", self.locked_accounts)?;
+        writeln!(writer, "checksum: sha256 {}", self.checksum)
+    }
+}
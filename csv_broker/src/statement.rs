@@ -0,0 +1,144 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    ops::Bound,
+    path::PathBuf,
+};
+
+use account::{AccountId, Amount, IsLocked, TransactionId};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use transaction_broker::{Engine, History, HistoryEntry, InputFormat};
+
+use crate::input_format;
+
+/// Exports the statements of the clients: every applied and rejected transaction,
+/// with the balances after the transaction.
+#[derive(Debug, Args)]
+pub struct StatementArgs {
+    /// Transactions file. Stdin if omitted or `-`.
+    input: Option<PathBuf>,
+    /// Format of the transactions, csv or jsonl. Detected from the input file extension
+    /// if omitted.
+    #[arg(long, value_name = "FORMAT")]
+    input_format: Option<InputFormat>,
+    /// Client of the statement. Repeat for many clients. All clients if omitted.
+    #[arg(long = "client", value_name = "ID")]
+    clients: Vec<u16>,
+    /// First sequence number of the statement.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    from: u64,
+    /// Last sequence number of the statement. Until the end of the input if omitted.
+    #[arg(long, value_name = "N")]
+    to: Option<u64>,
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// Output representation of a statement line. Statements of many clients
+/// are concatenated, ordered by client id.
+#[derive(Serialize)]
+struct StatementRecord {
+    sequence: u64,
+    client: AccountId,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    tx: TransactionId,
+    amount: Option<Amount>,
+    /// Reason the transaction was rejected, empty if it was applied.
+    error: Option<String>,
+    available: Amount,
+    held: Amount,
+    total: Option<Amount>,
+    locked: IsLocked,
+}
+
+impl From<&HistoryEntry> for StatementRecord {
+    fn from(entry: &HistoryEntry) -> Self {
+        let (kind, tx, amount) = entry.transaction.kind.fields();
+
+        StatementRecord {
+            sequence: entry.sequence,
+            client: entry.state.id.clone(),
+            kind,
+            tx: tx.clone(),
+            amount: amount.cloned(),
+            error: entry.error.as_ref().map(ToString::to_string),
+            available: entry.state.available.clone(),
+            held: entry.state.held.clone(),
+            total: entry.state.total().ok(),
+            locked: entry.state.is_locked.clone(),
+        }
+    }
+}
+
+/// Process the transactions with the blocking engine, keeping the history,
+/// and write the statements to stdout.
+pub fn export_statements(args: &StatementArgs) -> io::Result<()> {
+    let input: Box<dyn Read> = match &args.input {
+        Some(path) if path.as_os_str() != "-" => Box::new(File::open(path)?),
+        _ => Box::new(io::stdin().lock()),
+    };
+
+    let mut engine = Engine::default().with_history();
+    let format = input_format(args.input.as_deref(), args.input_format);
+    for transaction in format.txs_from_reader_blocking(input) {
+        let _res = engine.apply(transaction);
+    }
+
+    let history = engine.history().expect("History must be kept");
+    let records = statement_records(history, args);
+
+    let mut output = io::stdout().lock();
+    match args.format {
+        Format::Csv => {
+            let mut wtr = csv::Writer::from_writer(output);
+            for record in records {
+                wtr.serialize(record)?;
+            }
+            wtr.flush()
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut output, &records.collect::<Vec<_>>())?;
+            writeln!(output)
+        }
+    }
+}
+
+fn statement_records<'a>(
+    history: &'a History,
+    args: &'a StatementArgs,
+) -> impl Iterator<Item = StatementRecord> + 'a {
+    let clients = if args.clients.is_empty() {
+        history.account_ids()
+    } else {
+        let mut clients = args
+            .clients
+            .iter()
+            .copied()
+            .map(AccountId)
+            .collect::<Vec<_>>();
+        clients.sort_unstable();
+        clients.dedup();
+        clients
+    };
+
+    let sequences = (
+        Bound::Included(args.from),
+        args.to.map_or(Bound::Unbounded, Bound::Included),
+    );
+
+    clients.into_iter().flat_map(move |client| {
+        history
+            .statement(&client, sequences)
+            .iter()
+            .map(StatementRecord::from)
+    })
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "parquet")]
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use arrow_array::{Array, Decimal128Array, RecordBatch};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+/// Run the broker on the transactions given through stdin, returning its stdout.
+fn csv_broker(args: &[&str], transactions: &str) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_csv_broker"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(transactions.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    output.stdout
+}
+
+fn read_parquet(bytes: Vec<u8>) -> RecordBatch {
+    let mut batches = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(bytes))
+        .unwrap()
+        .build()
+        .unwrap();
+    let batch = batches.next().unwrap().unwrap();
+    assert!(batches.next().is_none());
+    batch
+}
+
+fn decimals(batch: &RecordBatch, column: &str) -> Vec<Option<i128>> {
+    batch
+        .column_by_name(column)
+        .unwrap()
+        .as_any()
+        .downcast_ref::<Decimal128Array>()
+        .unwrap()
+        .iter()
+        .collect()
+}
+
+#[test]
+fn parquet_amounts_over_precision_are_null() {
+    let rejections = std::env::temp_dir().join(format!(
+        "parquet_amounts_over_precision_{}.parquet",
+        std::process::id()
+    ));
+    let output = csv_broker(
+        &[
+            "--output-format",
+            "parquet",
+            "--rejections",
+            rejections.to_str().unwrap(),
+        ],
+        "\
+type,client,tx,amount
+deposit,1,1,1000000000000000000000000.0
+deposit,2,2,1.5
+withdraw,2,3,2000000000000000000000000.0
+",
+    );
+
+    let accounts = read_parquet(output);
+    assert_eq!(decimals(&accounts, "available"), [None, Some(15_000)]);
+    assert_eq!(decimals(&accounts, "held"), [Some(0), Some(0)]);
+    assert_eq!(decimals(&accounts, "total"), [None, Some(15_000)]);
+
+    let rejections_batch = read_parquet(std::fs::read(&rejections).unwrap());
+    std::fs::remove_file(&rejections).unwrap();
+    assert_eq!(decimals(&rejections_batch, "amount"), [None]);
+    assert_eq!(
+        rejections_batch
+            .column_by_name("type")
+            .unwrap()
+            .null_count(),
+        0
+    );
+}
@@ -0,0 +1,110 @@
+//! Apply transactions deserialized from arbitrary csv input to their accounts and
+//! check the account invariants after every step.
+#![no_main]
+
+use std::collections::{HashMap, HashSet};
+
+use account::TransactionKind;
+use account::{Account, AccountId, Amount, Error, IsLocked, Transaction, TransactionId};
+use libfuzzer_sys::fuzz_target;
+
+/// Bookkeeping of the accepted transactions of a single account.
+struct Accepted {
+    account: Account,
+    /// deposits - withdrawals - charge backs, at the fixed point scale of the amounts.
+    /// Held funds can push the total above `Amount::MAX`, so it is kept wider.
+    total: u128,
+    deposits: HashMap<TransactionId, Amount>,
+    disputed: HashSet<TransactionId>,
+}
+
+impl Accepted {
+    fn new(account_id: AccountId) -> Self {
+        Accepted {
+            account: Account::from_id(account_id),
+            total: 0,
+            deposits: HashMap::new(),
+            disputed: HashSet::new(),
+        }
+    }
+
+    fn apply(&mut self, transaction: Transaction) {
+        let before = self.account.state().clone();
+        let result = self.account.try_apply_transaction(transaction.clone());
+
+        if before.is_locked == IsLocked::Locked {
+            assert_eq!(result, Err(Error::LockedAccount));
+        }
+
+        match result {
+            Ok(_events) => self.record(&transaction),
+            Err(_) => assert_eq!(self.account.state(), &before),
+        }
+
+        let total = Amount::from_fixed_point(self.total, Amount::DECIMAL_POINTS)
+            .map_err(|_e| Error::TotalOverflow);
+        assert_eq!(self.account.state().total(), total);
+        assert_eq!(self.account.state().held, self.held());
+    }
+
+    fn record(&mut self, transaction: &Transaction) {
+        match &transaction.kind {
+            TransactionKind::Deposit(deposit) => {
+                self.total += deposit.amount().to_fixed_point();
+                self.deposits
+                    .insert(deposit.to_tx_id(), deposit.to_amount());
+            }
+            TransactionKind::Withdraw(withdraw) => {
+                self.total -= withdraw.amount().to_fixed_point();
+            }
+            TransactionKind::Dispute(dispute) => {
+                self.disputed.insert(dispute.target_tx_id.clone());
+            }
+            TransactionKind::Resolve(resolve) => {
+                self.disputed.remove(&resolve.target_tx_id);
+            }
+            TransactionKind::ChargeBack(charge_back) => {
+                self.disputed.remove(&charge_back.target_tx_id);
+                let amount = &self.deposits[&charge_back.target_tx_id];
+                self.total -= amount.to_fixed_point();
+            }
+        }
+    }
+
+    /// Sum of the currently disputed deposits.
+    fn held(&self) -> Amount {
+        self.disputed.iter().fold(Amount::MIN, |held, tx_id| {
+            held.checked_add(&self.deposits[tx_id]).unwrap()
+        })
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut accounts: HashMap<AccountId, Accepted> = HashMap::new();
+
+    let mut primary_tx_ids = HashSet::new();
+
+    let mut reader = csv::Reader::from_reader(data);
+    for transaction in reader.deserialize::<Transaction>().flatten() {
+        // Uniqueness of primary transaction ids is assumed by the accounts.
+        // Amounts are tracked at the fixed point scale of the accounts.
+        let primary = match &transaction.kind {
+            TransactionKind::Deposit(deposit) => Some((deposit.to_tx_id(), deposit.amount())),
+            TransactionKind::Withdraw(withdraw) => Some((withdraw.to_tx_id(), withdraw.amount())),
+            _ => None,
+        };
+        if let Some((tx_id, amount)) = primary {
+            let is_fixed_point =
+                Amount::from_fixed_point(amount.to_fixed_point(), Amount::DECIMAL_POINTS)
+                    .is_ok_and(|fixed_point| &fixed_point == amount);
+            if !is_fixed_point || !primary_tx_ids.insert(tx_id) {
+                continue;
+            }
+        }
+
+        accounts
+            .entry(transaction.target_account_id.clone())
+            .or_insert_with_key(|account_id| Accepted::new(account_id.clone()))
+            .apply(transaction);
+    }
+});
@@ -0,0 +1,13 @@
+//! Deserialize arbitrary input through the same path as `txs_from_csv`.
+//! Invalid records must be skipped, never panic.
+#![no_main]
+
+use async_std::task;
+use futures::StreamExt;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let reader = futures::io::Cursor::new(data.to_vec());
+
+    task::block_on(transaction_broker::txs_from_reader(reader).for_each(|_tx| async {}));
+});
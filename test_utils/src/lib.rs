@@ -0,0 +1,8 @@
+mod utils;
+
+pub use utils::{
+    generate_deposit_dispute_resolve, generate_deposit_withdraw, generate_deposits,
+    generate_deposits_dispute_resolve_many_acc, generate_deposits_many_acc,
+    generate_deposits_withdraw_many_acc, generate_hot_account, generate_seed_corpus, pattern_iter,
+    Interleave, TransactionRequestCompressed, TransposeFlatten,
+};
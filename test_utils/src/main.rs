@@ -1,13 +1,9 @@
-mod utils;
-
-use utils::{
-    generate_deposit_dispute_resolve, generate_deposit_withdraw,
+use test_utils::{
+    generate_deposit_dispute_resolve, generate_deposit_withdraw, generate_deposits,
     generate_deposits_dispute_resolve_many_acc, generate_deposits_many_acc,
-    generate_deposits_withdraw_many_acc,
+    generate_deposits_withdraw_many_acc, generate_hot_account, generate_seed_corpus,
 };
 
-use crate::utils::generate_deposits;
-
 fn main() {
     let _res = std::fs::create_dir_all("test_data/inputs");
     let _res = std::fs::create_dir("test_data/outputs");
@@ -21,4 +17,9 @@ fn main() {
     generate_deposits_dispute_resolve_many_acc(
         "test_data/inputs/deposit_dispute_resolve_many_acc.csv",
     );
+
+    generate_hot_account("test_data/inputs/hot_account.csv");
+
+    generate_seed_corpus("fuzz/corpus/csv_transactions");
+    generate_seed_corpus("fuzz/corpus/account_transactions");
 }
@@ -14,8 +14,7 @@ fn generate_test_csv(file_name: &str, tx_iter: impl Iterator<Item = Transaction>
 
     let test_file = File::create(file_name).unwrap();
 
-    // TODO: tabwritter for pretty output
-    // https://docs.rs/tabwriter/1.2.1/tabwriter/
+    // plain csv, the benches parse the generated files
     let mut wtr = csv::WriterBuilder::new().from_writer(test_file);
 
     for tx in tx_iter {
@@ -27,7 +26,7 @@ fn generate_test_csv(file_name: &str, tx_iter: impl Iterator<Item = Transaction>
 
 /// Minimal transaction record data for use in test case generation.
 /// Transaction id and Account id are provided during generation.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum TransactionRequestCompressed {
     Deposit(u64),    // amount
     Withdraw(u64),   // amount
@@ -177,15 +176,114 @@ pub fn generate_deposits_dispute_resolve_many_acc(file_name: &str) {
     )
 }
 
+/// Skewed workload. A single hot account receives 99% of the transactions,
+/// the rest is spread over 50 cold accounts.
+pub fn generate_hot_account(file_name: &str) {
+    let hot_account = pattern_iter(
+        AccountId(0),
+        vec![
+            TransactionRequestCompressed::Deposit(2),
+            TransactionRequestCompressed::Withdraw(1),
+        ],
+        100_000,
+    );
+
+    let mut accounts: Vec<Box<dyn Iterator<Item = Transaction>>> = vec![Box::new(hot_account)];
+    for account_id in 1..=50 {
+        accounts.push(Box::new(pattern_iter(
+            AccountId(account_id),
+            vec![TransactionRequestCompressed::Deposit(1)],
+            40,
+        )));
+    }
+
+    // every 100th transaction goes to a cold account
+    let schedule = (0..202_000).map(|index| {
+        if index % 100 == 0 {
+            1 + (index / 100) % 50
+        } else {
+            0
+        }
+    });
+
+    generate_test_csv(file_name, Interleave::new(accounts, schedule))
+}
+
+/// Write small csv files, covering all transaction patterns, into the corpus directory.
+/// Fuzz targets use these files as their seed corpus.
+pub fn generate_seed_corpus(corpus_dir: &str) {
+    let _res = std::fs::create_dir_all(corpus_dir);
+    let file_name = |name: &str| format!("{corpus_dir}/{name}.csv");
+
+    generate_test_csv(
+        &file_name("deposit"),
+        pattern_iter(
+            AccountId(0),
+            vec![TransactionRequestCompressed::Deposit(1)],
+            4,
+        ),
+    );
+    generate_test_csv(
+        &file_name("deposit_withdraw"),
+        pattern_iter(
+            AccountId(0),
+            vec![
+                TransactionRequestCompressed::Deposit(2),
+                TransactionRequestCompressed::Withdraw(3),
+            ],
+            2,
+        ),
+    );
+    generate_test_csv(
+        &file_name("deposit_dispute_resolve"),
+        pattern_iter(
+            AccountId(0),
+            vec![
+                TransactionRequestCompressed::Deposit(2),
+                TransactionRequestCompressed::Dispute(0),
+                TransactionRequestCompressed::Resolve(0),
+            ],
+            2,
+        ),
+    );
+    generate_test_csv(
+        &file_name("deposit_dispute_charge_back"),
+        pattern_iter(
+            AccountId(0),
+            vec![
+                TransactionRequestCompressed::Deposit(2),
+                TransactionRequestCompressed::Deposit(5),
+                TransactionRequestCompressed::Dispute(1),
+                TransactionRequestCompressed::ChargeBack(1),
+            ],
+            2,
+        ),
+    );
+    generate_test_csv(
+        &file_name("deposit_withdraw_dispute_many_acc"),
+        TransposeFlatten::new(
+            3,
+            vec![
+                TransactionRequestCompressed::Deposit(7),
+                TransactionRequestCompressed::Withdraw(2),
+                TransactionRequestCompressed::Dispute(0),
+                TransactionRequestCompressed::Dispute(1),
+                TransactionRequestCompressed::Resolve(0),
+            ],
+            2,
+        ),
+    );
+}
+
 /// flatten account tx in a round robin fashion
-struct TransposeFlatten<I> {
+pub struct TransposeFlatten<I> {
     accounts: Vec<I>,
     index: usize,
     len: u16,
 }
 
 impl TransposeFlatten<Box<dyn Iterator<Item = Transaction>>> {
-    fn new(
+    pub fn new(
         num_of_acc: u16,
         pattern: Vec<TransactionRequestCompressed>,
         num_of_cycles: u32,
@@ -223,3 +321,58 @@ where
         next
     }
 }
+
+/// Flatten account tx, taking the next tx from the account picked by the schedule.
+/// Schedule values are taken modulo the number of accounts. Picks of exhausted accounts
+/// are skipped. Once the schedule runs out the remaining tx are flattened in
+/// a round robin fashion.
+pub struct Interleave<I, S> {
+    accounts: Vec<I>,
+    schedule: S,
+    index: usize,
+}
+
+impl<I, S> Interleave<I, S>
+where
+    I: Iterator<Item = Transaction>,
+    S: Iterator<Item = usize>,
+{
+    pub fn new(accounts: Vec<I>, schedule: S) -> Self {
+        Self {
+            accounts,
+            schedule,
+            index: 0,
+        }
+    }
+}
+
+impl<I, S> Iterator for Interleave<I, S>
+where
+    I: Iterator<Item = Transaction>,
+    S: Iterator<Item = usize>,
+{
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.accounts.len();
+        if len == 0 {
+            return None;
+        }
+
+        while let Some(pick) = self.schedule.next() {
+            if let Some(tx) = self.accounts[pick % len].next() {
+                return Some(tx);
+            }
+        }
+
+        for _ in 0..len {
+            let next = self.accounts[self.index].next();
+            self.index = (self.index + 1) % len;
+            if next.is_some() {
+                return next;
+            }
+        }
+
+        None
+    }
+}
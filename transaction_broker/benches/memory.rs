@@ -0,0 +1,237 @@
+//! Peak heap allocation of the brokers. Kept apart from the time benches, since the
+//! counting allocator slows down every allocation of the process.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[cfg(not(feature = "rt-tokio"))]
+use criterion::async_executor::AsyncStdExecutor;
+use criterion::{
+    criterion_group, criterion_main,
+    measurement::{Measurement, ValueFormatter},
+    BenchmarkGroup, Criterion, Throughput,
+};
+use transaction_broker::{
+    process_csv_txs_with_broker, ActorBroker, Backpressure, Broker, SequentialBroker, ShardedBroker,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_measurement(PeakAllocation).sample_size(10);
+    targets = bench_hot_account_memory,
+}
+criterion_main!(benches);
+
+/// Bytes currently allocated.
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+/// Most bytes allocated at once since the last [`PeakAllocation::start`].
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+/// System allocator counting the allocated bytes.
+struct CountingAllocator;
+
+impl CountingAllocator {
+    fn allocated(size: usize) {
+        let allocated = ALLOCATED.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+    }
+
+    fn freed(size: usize) {
+        ALLOCATED.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::freed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                Self::allocated(new_size - layout.size());
+            } else {
+                Self::freed(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Peak of the heap allocated bytes, above the bytes allocated at the start.
+struct PeakAllocation;
+
+impl Measurement for PeakAllocation {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> Self::Intermediate {
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        PEAK.store(allocated, Ordering::Relaxed);
+        allocated
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        PEAK.load(Ordering::Relaxed).saturating_sub(start)
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl BytesFormatter {
+    fn scale(typical_value: f64, values: &mut [f64], units: [&'static str; 4]) -> &'static str {
+        let (denominator, unit) = if typical_value < 1024.0 {
+            (1.0, units[0])
+        } else if typical_value < 1024.0 * 1024.0 {
+            (1024.0, units[1])
+        } else if typical_value < 1024.0 * 1024.0 * 1024.0 {
+            (1024.0 * 1024.0, units[2])
+        } else {
+            (1024.0 * 1024.0 * 1024.0, units[3])
+        };
+
+        for value in values {
+            *value /= denominator;
+        }
+        unit
+    }
+}
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        Self::scale(typical_value, values, ["B", "KiB", "MiB", "GiB"])
+    }
+
+    fn scale_throughputs(
+        &self,
+        typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (Throughput::Bytes(elements)
+        | Throughput::BytesDecimal(elements)
+        | Throughput::Elements(elements)) = *throughput;
+
+        for value in values.iter_mut() {
+            *value /= elements as f64;
+        }
+        Self::scale(
+            typical_value / elements as f64,
+            values,
+            ["B/elem", "KiB/elem", "MiB/elem", "GiB/elem"],
+        )
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+/// Executor of the runtime selected by the features. Tokio is used if both are enabled.
+#[cfg(not(feature = "rt-tokio"))]
+fn executor() -> AsyncStdExecutor {
+    AsyncStdExecutor
+}
+
+#[cfg(feature = "rt-tokio")]
+fn executor() -> tokio::runtime::Runtime {
+    tokio::runtime::Runtime::new().expect("Must be able to start the runtime")
+}
+
+/// Peak allocation of processing the file with the broker, summed over the iterations
+/// so criterion reports the mean peak of an iteration.
+async fn peak_allocation(broker: &impl Broker, input: &str, output: &str, iters: u64) -> u64 {
+    let mut peaks = 0;
+    for _ in 0..iters {
+        let start = PeakAllocation.start();
+        process_csv_txs_with_broker(broker, input, output).await;
+        peaks += PeakAllocation.end(start);
+    }
+    peaks
+}
+
+fn bench_hot_account_memory(c: &mut Criterion<PeakAllocation>) {
+    // 1 hot account, 200000 tx
+    // 50 cold accounts, 40 tx per acc
+    // queued transactions are the memory that grows with a skewed input
+    let mut group = c.benchmark_group("hot account memory");
+
+    let unbounded = ActorBroker::default();
+    let bounded = ActorBroker {
+        backpressure: Backpressure {
+            channel_capacity: Some(1024),
+            max_in_flight: Some(4096),
+            ..Backpressure::default()
+        },
+        ..ActorBroker::default()
+    };
+    let sequential = SequentialBroker::default();
+    let unbounded_sharded = ShardedBroker::default();
+    let bounded_sharded = ShardedBroker {
+        channel_capacity: Some(1024),
+        ..ShardedBroker::default()
+    };
+
+    bench_peak_allocation(&mut group, "unbounded actor", &unbounded);
+    bench_peak_allocation(&mut group, "bounded actor", &bounded);
+    bench_peak_allocation(&mut group, "sync", &sequential);
+    bench_peak_allocation(&mut group, "unbounded sharded", &unbounded_sharded);
+    bench_peak_allocation(&mut group, "bounded sharded", &bounded_sharded);
+}
+
+/// Bench the peak allocation of processing the hot account file with the broker.
+fn bench_peak_allocation(
+    group: &mut BenchmarkGroup<PeakAllocation>,
+    id: &str,
+    broker: &impl Broker,
+) {
+    group.bench_function(id, |b| {
+        b.to_async(executor()).iter_custom(|iters| {
+            peak_allocation(
+                broker,
+                "../test_data/inputs/hot_account.csv",
+                "../test_data/outputs/hot_account.csv",
+                iters,
+            )
+        });
+    });
+}
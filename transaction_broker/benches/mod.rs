@@ -1,154 +1,179 @@
+#[cfg(not(feature = "rt-tokio"))]
+use criterion::async_executor::AsyncStdExecutor;
 use criterion::{
-    async_executor::AsyncStdExecutor, criterion_group, criterion_main, Criterion, Throughput,
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, Criterion, Throughput,
 };
+use futures::StreamExt;
 use pprof::criterion::{Output, PProfProfiler};
-use transaction_broker::{process_csv_txs, process_csv_txs_sync};
+use transaction_broker::{
+    process_csv_txs_with_broker, txs_from_csv_with_parser, ActorBroker, Backpressure, Broker,
+    CsvParser, ParallelParsing, SequentialBroker, ShardedBroker,
+};
 
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = bench_deposit, bench_deposit_withdraw, bench_deposit_dispute_resolve, bench_deposit_many_acc, bench_deposit_withdraw_many_acc, bench_deposit_dispute_resolve_many_acc,
+    targets = bench_deposit, bench_deposit_withdraw, bench_deposit_dispute_resolve, bench_deposit_many_acc, bench_deposit_withdraw_many_acc, bench_deposit_dispute_resolve_many_acc, bench_hot_account, bench_parsing,
 }
 criterion_main!(benches);
 
-// TODO: https://www.jibbow.com/posts/criterion-flamegraphs/ try it
-fn bench_deposit(c: &mut Criterion) {
-    let mut group = c.benchmark_group("deposits");
+/// Executor of the runtime selected by the features. Tokio is used if both are enabled.
+#[cfg(not(feature = "rt-tokio"))]
+fn executor() -> AsyncStdExecutor {
+    AsyncStdExecutor
+}
 
-    group.throughput(Throughput::Elements(180));
+#[cfg(feature = "rt-tokio")]
+fn executor() -> tokio::runtime::Runtime {
+    tokio::runtime::Runtime::new().expect("Must be able to start the runtime")
+}
 
-    group.bench_function("deposits", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs(
-                "../test_data/inputs/deposit.csv",
-                "../test_data/outputs/deposit.csv",
-            )
-        });
-    });
-    group.bench_function("deposits sync", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs_sync(
-                "../test_data/inputs/deposit.csv",
-                "../test_data/outputs/deposit.csv",
-            )
-        });
+/// Bench processing the input file with the broker.
+fn bench_broker(
+    group: &mut BenchmarkGroup<WallTime>,
+    id: &str,
+    broker: &impl Broker,
+    file_name: &str,
+) {
+    let input = format!("../test_data/inputs/{file_name}");
+    let output = format!("../test_data/outputs/{file_name}");
+
+    group.bench_function(id, |b| {
+        b.to_async(executor())
+            .iter(|| process_csv_txs_with_broker(broker, &input, &output));
     });
 }
 
-fn bench_deposit_withdraw(c: &mut Criterion) {
-    let mut group = c.benchmark_group("deposit, withdraw");
-    group.throughput(Throughput::Elements(180));
-
-    group.bench_function("deposit withdraw", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs(
-                "../test_data/inputs/deposit_withdraw.csv",
-                "../test_data/outputs/deposit_withdraw.csv",
-            )
-        });
-    });
+/// Bench the default actor, sequential and sharded brokers on the input file.
+fn bench_brokers(c: &mut Criterion, group_name: &str, file_name: &str, elements: u64) {
+    let mut group = c.benchmark_group(group_name);
+    group.throughput(Throughput::Elements(elements));
 
-    group.bench_function("deposit withdraw sync", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs_sync(
-                "../test_data/inputs/deposit_withdraw.csv",
-                "../test_data/outputs/deposit_withdraw.csv",
-            )
-        });
-    });
+    bench_broker(&mut group, "actor", &ActorBroker::default(), file_name);
+    bench_broker(&mut group, "sync", &SequentialBroker::default(), file_name);
+    bench_broker(&mut group, "sharded", &ShardedBroker::default(), file_name);
 }
 
-fn bench_deposit_dispute_resolve(c: &mut Criterion) {
-    let mut group = c.benchmark_group("deposit, dispute, resolve");
-    group.throughput(Throughput::Elements(180));
-
-    group.bench_function("deposit dispute withdraw", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs(
-                "../test_data/inputs/deposit_dispute_resolve.csv",
-                "../test_data/outputs/deposit_dispute_resolve_accounts.csv",
-            )
-        });
-    });
+// TODO: https://www.jibbow.com/posts/criterion-flamegraphs/ try it
+fn bench_deposit(c: &mut Criterion) {
+    bench_brokers(c, "deposits", "deposit.csv", 180);
+}
 
-    group.bench_function("deposit dispute withdraw sync", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs_sync(
-                "../test_data/inputs/deposit_dispute_resolve.csv",
-                "../test_data/outputs/deposit_dispute_resolve_accounts.csv",
-            )
-        });
-    });
+fn bench_deposit_withdraw(c: &mut Criterion) {
+    bench_brokers(c, "deposit, withdraw", "deposit_withdraw.csv", 180);
+}
+
+fn bench_deposit_dispute_resolve(c: &mut Criterion) {
+    bench_brokers(
+        c,
+        "deposit, dispute, resolve",
+        "deposit_dispute_resolve.csv",
+        180,
+    );
 }
 
 fn bench_deposit_many_acc(c: &mut Criterion) {
-    let mut group = c.benchmark_group("deposits - multiple accs");
-    group.throughput(Throughput::Elements(18000));
-
-    group.bench_function("deposits", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs(
-                "../test_data/inputs/deposit_many_acc.csv",
-                "../test_data/outputs/deposit_accounts_many_acc.csv",
-            )
-        });
-    });
-    group.bench_function("deposits sync", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs_sync(
-                "../test_data/inputs/deposit_many_acc.csv",
-                "../test_data/outputs/deposit_many_acc.csv",
-            )
-        });
-    });
+    bench_brokers(c, "deposits - multiple accs", "deposit_many_acc.csv", 18000);
 }
 
 fn bench_deposit_withdraw_many_acc(c: &mut Criterion) {
     // 100 acc
     // 180 tx per acc
-    let mut group = c.benchmark_group("deposit, withdraw - multiple acc");
-    group.throughput(Throughput::Elements(18000));
-
-    group.bench_function("deposit withdraw", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs(
-                "../test_data/inputs/deposit_withdraw_many_acc.csv",
-                "../test_data/outputs/deposit_withdraw_many_acc.csv",
-            )
-        });
-    });
-
-    group.bench_function("deposit withdraw sync", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs_sync(
-                "../test_data/inputs/deposit_withdraw_many_acc.csv",
-                "../test_data/outputs/deposit_withdraw_many_acc.csv",
-            )
-        });
-    });
+    bench_brokers(
+        c,
+        "deposit, withdraw - multiple acc",
+        "deposit_withdraw_many_acc.csv",
+        18000,
+    );
 }
 
 fn bench_deposit_dispute_resolve_many_acc(c: &mut Criterion) {
     // 180 tx
     // 100 acc
-    let mut group = c.benchmark_group("deposit, dispute, resolve - many acc");
-    group.throughput(Throughput::Elements(18000));
-
-    group.bench_function("deposit dispute withdraw", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs(
-                "../test_data/inputs/deposit_dispute_resolve_many_acc.csv",
-                "../test_data/outputs/deposit_dispute_resolve_many_acc.csv",
-            )
-        });
-    });
+    bench_brokers(
+        c,
+        "deposit, dispute, resolve - many acc",
+        "deposit_dispute_resolve_many_acc.csv",
+        18000,
+    );
+}
 
-    group.bench_function("deposit dispute withdraw sync", |b| {
-        b.to_async(AsyncStdExecutor).iter(|| {
-            process_csv_txs_sync(
-                "../test_data/inputs/deposit_dispute_resolve_many_acc.csv",
-                "../test_data/outputs/deposit_dispute_resolve_many_acc.csv",
-            )
-        });
-    });
+fn bench_hot_account(c: &mut Criterion) {
+    // 1 hot account, 200000 tx
+    // 50 cold accounts, 40 tx per acc
+    let mut group = c.benchmark_group("hot account");
+    group.throughput(Throughput::Elements(202_000));
+    group.sample_size(10);
+
+    let bounded_actor = ActorBroker {
+        backpressure: Backpressure {
+            channel_capacity: Some(1024),
+            max_in_flight: Some(4096),
+            ..Backpressure::default()
+        },
+        ..ActorBroker::default()
+    };
+    let bounded_sharded = ShardedBroker {
+        channel_capacity: Some(1024),
+        ..ShardedBroker::default()
+    };
+
+    let file_name = "hot_account.csv";
+    bench_broker(
+        &mut group,
+        "unbounded actor",
+        &ActorBroker::default(),
+        file_name,
+    );
+    bench_broker(&mut group, "bounded actor", &bounded_actor, file_name);
+    bench_broker(&mut group, "sync", &SequentialBroker::default(), file_name);
+    bench_broker(
+        &mut group,
+        "unbounded sharded",
+        &ShardedBroker::default(),
+        file_name,
+    );
+    bench_broker(&mut group, "bounded sharded", &bounded_sharded, file_name);
+}
+
+fn bench_parsing(c: &mut Criterion) {
+    let inputs = [
+        ("deposits - multiple accs", "deposit_many_acc.csv", 18000),
+        (
+            "deposit, withdraw - multiple acc",
+            "deposit_withdraw_many_acc.csv",
+            18000,
+        ),
+        (
+            "deposit, dispute, resolve - many acc",
+            "deposit_dispute_resolve_many_acc.csv",
+            18000,
+        ),
+        ("hot account", "hot_account.csv", 202_000),
+    ];
+    let parsers = [
+        ("serde", CsvParser::Serde),
+        ("parallel", CsvParser::Parallel(ParallelParsing::default())),
+        ("byte record", CsvParser::ByteRecord),
+    ];
+
+    for (group_name, input, elements) in inputs {
+        let mut group = c.benchmark_group(format!("parsing - {group_name}"));
+        group.throughput(Throughput::Elements(elements));
+        group.sample_size(10);
+
+        let input = format!("../test_data/inputs/{input}");
+
+        for (name, parser) in &parsers {
+            group.bench_function(*name, |b| {
+                b.to_async(executor()).iter(|| async {
+                    txs_from_csv_with_parser(&input, parser)
+                        .await
+                        .expect("Must be able to open file")
+                        .count()
+                        .await
+                });
+            });
+        }
+    }
 }
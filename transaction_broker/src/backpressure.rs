@@ -0,0 +1,104 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use async_channel::{self as channel, Receiver, Sender};
+
+/// Limits on the transactions queued by the async broker. Unbounded by default.
+///
+/// Once a limit is reached the broker stops reading the input stream until
+/// the accounts catch up.
+///
+/// # Panics
+/// Limits must be greater than zero.
+#[derive(Debug, Clone, Default)]
+pub struct Backpressure {
+    /// Capacity of each account channel.
+    pub channel_capacity: Option<usize>,
+    /// Maximum number of transactions dispatched to the accounts, but not yet applied.
+    pub max_in_flight: Option<usize>,
+    /// Queue metrics, updated while the broker runs.
+    pub metrics: Arc<QueueMetrics>,
+}
+
+impl Backpressure {
+    pub(crate) fn account_channel<T>(&self) -> (Sender<T>, Receiver<T>) {
+        match self.channel_capacity {
+            Some(capacity) => channel::bounded(capacity),
+            None => channel::unbounded(),
+        }
+    }
+
+    pub(crate) fn in_flight(&self) -> InFlight {
+        InFlight {
+            permits: self.max_in_flight.map(channel::bounded),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// Queue metrics of the async broker.
+#[derive(Debug, Default)]
+pub struct QueueMetrics {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    max_queue_depth: AtomicUsize,
+}
+
+impl QueueMetrics {
+    /// Number of transactions dispatched to the accounts, but not yet applied.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Highest number of transactions in flight observed.
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Highest number of transactions queued on a single account channel observed.
+    pub fn max_queue_depth(&self) -> usize {
+        self.max_queue_depth.load(Ordering::Relaxed)
+    }
+}
+
+/// Tracks the transactions in flight. Every dispatched transaction holds a permit
+/// until it is applied. Without a limit permits are always available.
+#[derive(Debug, Clone)]
+pub(crate) struct InFlight {
+    /// Channel holding one message per transaction in flight.
+    permits: Option<(Sender<()>, Receiver<()>)>,
+    metrics: Arc<QueueMetrics>,
+}
+
+impl InFlight {
+    /// Wait for a permit to dispatch a transaction.
+    pub(crate) async fn acquire(&self) {
+        if let Some((permits, _)) = &self.permits {
+            // the channel is never closed, both ends are owned by self
+            let _res = permits.send(()).await;
+        }
+
+        let in_flight = self.metrics.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        self.metrics
+            .max_in_flight
+            .fetch_max(in_flight, Ordering::Relaxed);
+    }
+
+    /// Release the permit of an applied transaction.
+    pub(crate) fn release(&self) {
+        // decrement first, so the counter never exceeds the number of permits
+        self.metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+
+        if let Some((_, permits)) = &self.permits {
+            let _res = permits.try_recv();
+        }
+    }
+
+    pub(crate) fn record_queue_depth(&self, queue_depth: usize) {
+        self.metrics
+            .max_queue_depth
+            .fetch_max(queue_depth, Ordering::Relaxed);
+    }
+}
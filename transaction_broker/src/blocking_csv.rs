@@ -0,0 +1,109 @@
+use std::io::{Read, Write};
+
+use account::{AccountState, AccountStateRecord, Transaction};
+
+use crate::engine::Engine;
+
+/// Process the transactions of the input csv with the engine and write
+/// the final account states into the output csv. Rejected transactions are ignored.
+///
+/// # Errors
+/// Writing the output fails.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn process_csv_txs_blocking(
+    mut engine: Engine,
+    input: impl Read,
+    output: impl Write,
+) -> csv::Result<()> {
+    for transaction in txs_from_csv_reader(input) {
+        let _res = engine.apply(transaction);
+
+        #[cfg(feature = "tracing")]
+        if _res.is_err() {
+            tracing::info!(err = ?_res, "Transaction rejected");
+        }
+    }
+
+    accounts_into_csv_writer(output, engine.into_states())
+}
+
+/// Deserialize csv records from the reader using serde.
+/// Reader is buffered. Fields are assigned based on headers.
+/// If a record can't be deserialized it is ignored.
+pub fn txs_from_csv_reader(reader: impl Read) -> impl Iterator<Item = Transaction> {
+    csv::Reader::from_reader(reader)
+        .into_deserialize()
+        .filter_map(|result| {
+            #[cfg(feature = "tracing")]
+            if result.is_err() {
+                tracing::error!(err = ?result, "Failed to deserialize record");
+            }
+
+            result.ok()
+        })
+}
+
+/// Write the account states as CSV records into the writer.
+/// Account states are written in the order they are received.
+/// Account states that can't be represented as a record are ignored.
+///
+/// # Errors
+/// Writing a record fails.
+pub fn accounts_into_csv_writer(
+    writer: impl Write,
+    account_states: impl IntoIterator<Item = AccountState>,
+) -> csv::Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+
+    for state in account_states {
+        let record = AccountStateRecord::try_from(state);
+
+        #[cfg(feature = "tracing")]
+        if record.is_err() {
+            tracing::error!(err = ?record, "Failed to serialize record");
+        }
+
+        if let Ok(record) = record {
+            wtr.serialize(record)?;
+        }
+    }
+
+    wtr.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccountOrder;
+
+    #[test]
+    fn process_csv() {
+        let input = "type,client,tx,amount
+deposit,2,1,10.5
+deposit,1,2,3
+withdraw,2,3,0.5
+withdraw,1,4,5
+dispute,1,2,
+not a record
+chargeback,1,2,
+";
+        let mut output = Vec::new();
+
+        process_csv_txs_blocking(
+            Engine::new(AccountOrder::ClientId),
+            input.as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked
+1,0,0,0,true
+2,10.0,0,10.0,false
+"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+#[cfg(feature = "async")]
+use std::future::Future;
+
+#[cfg(feature = "async")]
+use account::Account;
+#[cfg(feature = "async")]
+use account::AccountEvents;
+use account::{AccountEvent, AccountId, AccountState, Error, Transaction};
+
+use crate::tally::Tallies;
+#[cfg(feature = "async")]
+use async_channel::Sender;
+#[cfg(feature = "async")]
+use futures::Stream;
+
+/// Transaction processing engine. Applies a stream of transactions to the accounts
+/// they target.
+///
+/// Transactions targeting the same account must be applied in the order they are
+/// received. Transactions that fail to apply are rejected, they do not stop processing.
+#[cfg(feature = "async")]
+pub trait Broker {
+    /// Process the transactions until the stream ends.
+    fn process(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> impl Future<Output = BrokerOutput>;
+
+    /// Process the transactions until the stream ends. Returns the events of the applied
+    /// transactions, and the broker future.
+    ///
+    /// Events of an account are received in the order of its transactions. Events are
+    /// buffered until they are received, the broker is never blocked by the event stream.
+    fn process_with_events(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> (
+        impl Stream<Item = SequencedEvent>,
+        impl Future<Output = BrokerOutput>,
+    );
+}
+
+/// Order in which the brokers emit the final account states.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccountOrder {
+    /// Ascending client id.
+    #[default]
+    ClientId,
+    /// Order in which the clients first appear in the input stream.
+    FirstSeen,
+}
+
+impl AccountOrder {
+    /// Arrange the account ids, given in the order they were first seen.
+    pub(crate) fn arrange(self, mut first_seen: Vec<AccountId>) -> Vec<AccountId> {
+        if self == AccountOrder::ClientId {
+            first_seen.sort_unstable();
+        }
+
+        first_seen
+    }
+}
+
+/// Transaction that could not be applied to its target account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    /// Position of the transaction in the input stream.
+    pub sequence: u64,
+    pub transaction: Transaction,
+    pub error: Error,
+}
+
+/// Account event of an applied transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequencedEvent {
+    /// Position of the transaction in the input stream.
+    pub sequence: u64,
+    pub event: AccountEvent,
+}
+
+/// Final result of processing a transaction stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerOutput {
+    /// Account states, arranged by the requested [`AccountOrder`].
+    pub accounts: Vec<AccountState>,
+    /// Rejected transactions, ordered by their sequence number.
+    pub rejections: Vec<Rejection>,
+    /// Tallies of the applied and rejected transactions.
+    pub tallies: Tallies,
+}
+
+/// Apply the transaction, recording the failure as a rejection. Events are forwarded
+/// to the event stream, if there is one.
+#[cfg(feature = "async")]
+pub(crate) fn try_apply(
+    account: &mut Account,
+    sequence: u64,
+    transaction: Transaction,
+    events: Option<&Sender<SequencedEvent>>,
+    tallies: &mut Tallies,
+) -> Option<Rejection> {
+    let result = account.try_apply_transaction(transaction.clone());
+    tallies.record(&transaction, &result);
+
+    match result {
+        Ok(account_events) => {
+            forward_events(events, sequence, account_events);
+            None
+        }
+        Err(error) => Some(Rejection {
+            sequence,
+            transaction,
+            error,
+        }),
+    }
+}
+
+/// Forward the events of an applied transaction to the event stream, if there is one.
+///
+/// # Errors
+/// Events are dropped once the event stream is dropped.
+#[cfg(feature = "async")]
+pub(crate) fn forward_events(
+    events: Option<&Sender<SequencedEvent>>,
+    sequence: u64,
+    account_events: AccountEvents,
+) {
+    let Some(events) = events else {
+        return;
+    };
+
+    for event in account_events {
+        // the event channel is unbounded, sending fails only if the stream was dropped
+        let _res = events.try_send(SequencedEvent { sequence, event });
+    }
+}
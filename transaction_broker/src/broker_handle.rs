@@ -0,0 +1,63 @@
+use account::{AccountId, AccountState};
+use async_channel::{self as channel, Receiver, Sender};
+
+/// Account states at a point in the input stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Number of transactions applied to the accounts.
+    pub sequence: u64,
+    /// Account states, arranged by the broker's [`AccountOrder`](crate::AccountOrder).
+    pub accounts: Vec<AccountState>,
+}
+
+/// Requests handled by the broker between two transactions.
+#[derive(Debug)]
+pub(crate) enum Request {
+    /// Replies with the receiver of the account state, `None` if the account has not been seen.
+    Account(AccountId, Sender<Option<Receiver<AccountState>>>),
+    /// Replies with the sequence number and the receivers of all account states.
+    Snapshot(Sender<(u64, Vec<Receiver<AccountState>>)>),
+}
+
+/// Queries the accounts while the broker runs. Queries are answered between two transactions
+/// of the input stream.
+///
+/// All queries return `None` once the broker has finished.
+#[derive(Debug, Clone)]
+pub struct BrokerHandle {
+    requests: Sender<Request>,
+}
+
+impl BrokerHandle {
+    pub(crate) fn new() -> (Self, Receiver<Request>) {
+        let (requests, receiver) = channel::unbounded();
+        (BrokerHandle { requests }, receiver)
+    }
+
+    /// Current state of the account. `None` if the account has not been seen.
+    pub async fn account(&self, account_id: AccountId) -> Option<AccountState> {
+        let (reply, response) = channel::bounded(1);
+        self.requests
+            .send(Request::Account(account_id, reply))
+            .await
+            .ok()?;
+
+        let state = response.recv().await.ok()??;
+        state.recv().await.ok()
+    }
+
+    /// Consistent snapshot of all accounts. Every account state includes all the transactions
+    /// preceding the snapshot sequence number, and none following it.
+    pub async fn snapshot(&self) -> Option<Snapshot> {
+        let (reply, response) = channel::bounded(1);
+        self.requests.send(Request::Snapshot(reply)).await.ok()?;
+
+        let (sequence, states) = response.recv().await.ok()?;
+        let mut accounts = Vec::with_capacity(states.len());
+        for state in states {
+            accounts.push(state.recv().await.ok()?);
+        }
+
+        Some(Snapshot { sequence, accounts })
+    }
+}
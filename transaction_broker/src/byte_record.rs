@@ -0,0 +1,345 @@
+use account::{AccountId, Amount, Transaction, TransactionId};
+use csv_async::{AsyncReader, ByteRecord};
+use futures::{AsyncRead, Stream};
+
+/// Reasons a byte record can't be decoded into a transaction.
+// the details are only read through Debug, when tracing
+#[allow(dead_code)]
+#[derive(Debug)]
+enum DecodeError {
+    /// A column is not present in the headers.
+    MissingColumn(&'static str),
+    /// Field can't be decoded into the column type.
+    InvalidField(&'static str),
+    /// Fields are valid, but don't form a valid transaction.
+    Transaction(account::Error),
+}
+
+/// Position of the transaction fields in a record, as given by the headers.
+#[derive(Debug, Clone, Copy)]
+struct Columns {
+    r#type: usize,
+    client: usize,
+    tx: usize,
+    amount: Option<usize>,
+}
+
+impl Columns {
+    fn from_headers(headers: &ByteRecord) -> Result<Self, DecodeError> {
+        let position = |name: &[u8]| headers.iter().position(|header| header == name);
+        let required =
+            |name: &'static str| position(name.as_bytes()).ok_or(DecodeError::MissingColumn(name));
+
+        Ok(Columns {
+            r#type: required("type")?,
+            client: required("client")?,
+            tx: required("tx")?,
+            amount: position(b"amount"),
+        })
+    }
+}
+
+/// Decode csv byte records from the reader, without going through serde.
+/// Fields are assigned based on headers.
+/// If a record can't be decoded it is ignored.
+///
+/// Plain amounts are decoded as fixed point numbers. Other amounts, with an exponent,
+/// underscores or many digits, are deserialized the way serde does, so the decoded
+/// transactions match [`txs_from_reader`](crate::txs_from_reader).
+pub fn txs_from_reader_bytes(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+) -> impl Stream<Item = Transaction> {
+    let decoder = Decoder {
+        csv_reader: AsyncReader::from_reader(reader),
+        columns: None,
+        record: ByteRecord::new(),
+    };
+
+    futures::stream::unfold(decoder, |mut decoder| async move {
+        let transaction = decoder.next_transaction().await?;
+        Some((transaction, decoder))
+    })
+}
+
+/// Decodes the records one by one, reusing the record buffer.
+struct Decoder<R> {
+    csv_reader: AsyncReader<R>,
+    /// `None` until the headers are read.
+    columns: Option<Columns>,
+    record: ByteRecord,
+}
+
+impl<R: AsyncRead + Unpin + Send> Decoder<R> {
+    /// Next valid transaction. Returns `None` once the input is exhausted.
+    ///
+    /// # Errors
+    /// Invalid headers and read errors end the input.
+    async fn next_transaction(&mut self) -> Option<Transaction> {
+        let columns = match self.columns {
+            Some(columns) => columns,
+            None => {
+                let headers = self.csv_reader.byte_headers().await.ok()?;
+                let columns = Columns::from_headers(headers);
+
+                #[cfg(feature = "tracing")]
+                if columns.is_err() {
+                    tracing::error!(err = ?columns, "Failed to decode headers");
+                }
+
+                *self.columns.insert(columns.ok()?)
+            }
+        };
+
+        loop {
+            match self.csv_reader.read_byte_record(&mut self.record).await {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::error!(err = ?err, "Failed to read record");
+
+                    if err.is_io_error() {
+                        return None;
+                    }
+                    continue;
+                }
+            }
+
+            let transaction = decode(&self.record, &columns);
+
+            #[cfg(feature = "tracing")]
+            if transaction.is_err() {
+                tracing::error!(err = ?transaction, "Failed to decode record");
+            }
+
+            if let Ok(transaction) = transaction {
+                return Some(transaction);
+            }
+        }
+    }
+}
+
+fn decode(record: &ByteRecord, columns: &Columns) -> Result<Transaction, DecodeError> {
+    let field = |index: usize| record.get(index).unwrap_or_default();
+
+    let client =
+        AccountId(parse_uint(field(columns.client)).ok_or(DecodeError::InvalidField("client"))?);
+    let tx = TransactionId(parse_uint(field(columns.tx)).ok_or(DecodeError::InvalidField("tx"))?);
+    let amount = match columns.amount.map(field) {
+        None | Some(b"") => None,
+        Some(amount) => Some(parse_amount(amount).ok_or(DecodeError::InvalidField("amount"))?),
+    };
+
+    let amount = || amount.ok_or(DecodeError::Transaction(account::Error::MissingAmount));
+    let transaction = match field(columns.r#type) {
+        b"deposit" => Transaction::deposit(client, tx, amount()?),
+        b"withdraw" => Transaction::withdraw(client, tx, amount()?),
+        b"resolve" => Ok(Transaction::resolve(client, tx)),
+        b"dispute" => Ok(Transaction::dispute(client, tx)),
+        b"chargeback" => Ok(Transaction::charge_back(client, tx)),
+        _ => Err(account::Error::UnknownTransactionType),
+    };
+
+    transaction.map_err(DecodeError::Transaction)
+}
+
+/// Decode an unsigned integer. An optional `+` sign followed by ascii digits,
+/// as accepted by serde.
+fn parse_uint<T>(field: &[u8]) -> Option<T>
+where
+    T: TryFrom<u64>,
+{
+    let digits = field.strip_prefix(b"+").unwrap_or(field);
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut value = 0u64;
+    for byte in digits {
+        value = value
+            .checked_mul(10)?
+            .checked_add(u64::from(ascii_digit(*byte)?))?;
+    }
+
+    T::try_from(value).ok()
+}
+
+/// Most digits of an amount with a decimal point decoded as a fixed point number.
+/// Serde reads these amounts as `f64`, which keeps up to 15 significant digits exact.
+const FIXED_POINT_DIGITS: usize = 15;
+
+/// Decode a non negative decimal. An optional `+` sign followed by ascii digits,
+/// with an optional decimal point, is decoded as a fixed point number. Any other
+/// amount is deserialized with serde.
+fn parse_amount(field: &[u8]) -> Option<Amount> {
+    parse_fixed_point(field).or_else(|| {
+        ByteRecord::from(vec![field])
+            .deserialize::<Amount>(None)
+            .ok()
+    })
+}
+
+/// Decode the amounts serde reads exactly: integers that fit a `u64`, and numbers with
+/// a decimal point of up to [`FIXED_POINT_DIGITS`] digits and [`Amount::DECIMAL_POINTS`]
+/// decimals. `None` for any other amount, valid or not.
+fn parse_fixed_point(field: &[u8]) -> Option<Amount> {
+    let number = field.strip_prefix(b"+").unwrap_or(field);
+    let Some(point) = number.iter().position(|byte| *byte == b'.') else {
+        let integer = parse_uint::<u64>(field)?;
+        return Amount::from_fixed_point(u128::from(integer), 0).ok();
+    };
+
+    let (integer, fraction) = (&number[..point], &number[point + 1..]);
+    let digits = integer.len() + fraction.len();
+    if digits == 0
+        || digits > FIXED_POINT_DIGITS
+        || fraction.len() > Amount::DECIMAL_POINTS as usize
+    {
+        return None;
+    }
+
+    let mut mantissa = 0u128;
+    for byte in integer.iter().chain(fraction) {
+        mantissa = mantissa * 10 + u128::from(ascii_digit(*byte)?);
+    }
+
+    Amount::from_fixed_point(mantissa, fraction.len() as u32).ok()
+}
+
+fn ascii_digit(byte: u8) -> Option<u8> {
+    byte.is_ascii_digit().then(|| byte - b'0')
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{io::Cursor, StreamExt};
+    use proptest::prelude::*;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::rt;
+    use crate::txs_from_reader;
+
+    const RECORDS: &str = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.5
+withdraw,1,3,0.5
+dispute,2,2,
+resolve,2,2,
+deposit,3,4,10.12345
+deposit,3,5,+7
+deposit,65535,4294967295,.5
+chargeback,2,2,";
+
+    const INVALID_RECORDS: &str = "type,client,tx,amount
+not a record
+deposit,1,1
+deposit,1,2,
+deposit,1,3,-1.0
+deposit,1,4,1.0.0
+deposit,1,5,0
+withdrawal,1,6,1.0
+deposit,65536,7,1.0
+deposit,1,4294967296,1.0
+dispute,1,1,abc";
+
+    async fn parse(input: &str) -> Vec<Transaction> {
+        txs_from_reader_bytes(Cursor::new(input.to_owned()))
+            .collect()
+            .await
+    }
+
+    async fn parse_serde(input: &str) -> Vec<Transaction> {
+        txs_from_reader(Cursor::new(input.to_owned()))
+            .collect()
+            .await
+    }
+
+    #[test_case(RECORDS ; "Valid records")]
+    #[test_case(INVALID_RECORDS ; "Invalid records")]
+    #[test_case("client,amount,tx,type\n1,1.5,1,deposit\n2,,3,dispute" ; "Reordered columns")]
+    #[test_case("type,client,tx\ndeposit,1,1\ndispute,1,1" ; "Without amount column")]
+    #[test_case("" ; "Empty input")]
+    fn byte_records_match_serde(input: &str) {
+        rt::block_on(async {
+            assert_eq!(parse(input).await, parse_serde(input).await);
+        })
+    }
+
+    #[test]
+    fn missing_column_ends_input() {
+        rt::block_on(async {
+            assert_eq!(parse("type,client,amount\ndeposit,1,1.0").await, vec![]);
+        })
+    }
+
+    #[test_case(b"1" => Some(Amount::from_u64(1)) ; "Integer")]
+    #[test_case(b"1.5" => Amount::from_fixed_point(15, 1).ok() ; "Decimal")]
+    #[test_case(b"1." => Some(Amount::from_u64(1)) ; "Trailing point")]
+    #[test_case(b"0.000000000000000000000000000001" => Some(Amount::MIN) ; "Scale over max")]
+    #[test_case(b"18446744073709551615" => Amount::from_fixed_point(u64::MAX.into(), 0).ok() ; "Max u64")]
+    #[test_case(b"18446744073709551616" => None ; "Integer over u64")]
+    #[test_case(b"1000000000000000000000.000000000001" => Amount::from_fixed_point(10u128.pow(21), 0).ok() ; "Mantissa over 96 bits")]
+    #[test_case(b"12345678901234567.89" => Amount::from_fixed_point(12345678901234568, 0).ok() ; "Digits over f64 precision")]
+    #[test_case(b"." => None ; "Only point")]
+    #[test_case(b"1e5" => Some(Amount::from_u64(100_000)) ; "Exponent")]
+    #[test_case(b"1E-2" => Amount::from_fixed_point(1, 2).ok() ; "Negative exponent")]
+    #[test_case(b"1_000" => Some(Amount::from_u64(1_000)) ; "Underscores")]
+    #[test_case(b"-0" => Some(Amount::MIN) ; "Negative zero")]
+    #[test_case(b"inf" => None ; "Infinity")]
+    #[test_case(b" 1" => None ; "Leading space")]
+    fn amount_from_bytes(field: &[u8]) -> Option<Amount> {
+        parse_amount(field)
+    }
+
+    // amounts are range checked and rounded by the accounts, not while parsing
+    #[test_case(b"0.00005" ; "More decimals than the accounts keep")]
+    #[test_case(b"7922816251426433759354395.0336" ; "Over max")]
+    #[test_case(b"-1" ; "Negative")]
+    fn unchecked_amounts_are_deserialized_with_serde(field: &[u8]) {
+        let serde = ByteRecord::from(vec![field])
+            .deserialize::<Amount>(None)
+            .ok();
+
+        assert!(serde.is_some());
+        assert_eq!(parse_amount(field), serde);
+    }
+
+    fn field() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[+]?[0-9]{1,22}",
+            "[+-]?[0-9]{0,20}[.][0-9]{0,20}",
+            "[0-9]{1,3}([.][0-9]{1,3})?[eE][+-]?[0-9]{1,2}",
+            "[0-9_]{1,8}([.][0-9_]{1,4})?",
+            "[0-9.eE+_ -]{0,8}",
+            Just("inf".to_owned()),
+            Just("NaN".to_owned()),
+            Just("true".to_owned()),
+            Just("".to_owned()),
+        ]
+    }
+
+    fn record() -> impl Strategy<Value = String> {
+        let kind = prop_oneof![
+            Just("deposit"),
+            Just("withdraw"),
+            Just("dispute"),
+            Just("resolve"),
+            Just("chargeback"),
+            Just("withdrawal"),
+        ];
+
+        (kind, field(), field(), field())
+            .prop_map(|(kind, client, tx, amount)| format!("{kind},{client},{tx},{amount}"))
+    }
+
+    proptest! {
+        #[test]
+        fn byte_records_match_serde_on_any_field(records in proptest::collection::vec(record(), 1..32)) {
+            let input = format!("type,client,tx,amount\n{}", records.join("\n"));
+
+            let (bytes, serde) = rt::block_on(async { (parse(&input).await, parse_serde(&input).await) });
+            prop_assert_eq!(bytes, serde);
+        }
+    }
+}
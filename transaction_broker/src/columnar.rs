@@ -0,0 +1,290 @@
+#[cfg(feature = "parquet")]
+use std::io::Write;
+use std::sync::Arc;
+
+use account::{AccountState, Amount, IsLocked};
+use arrow_array::{
+    ArrayRef, BooleanArray, Decimal128Array, RecordBatch, StringArray, UInt16Array, UInt32Array,
+    UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+#[cfg(feature = "parquet")]
+use parquet::{arrow::ArrowWriter, errors::ParquetError};
+
+use crate::broker::Rejection;
+
+/// Precision of the amount columns. Amounts with more digits are written as null.
+pub const AMOUNT_PRECISION: u8 = 28;
+/// Scale of the amount columns, matching [`Amount::DECIMAL_POINTS`].
+pub const AMOUNT_SCALE: i8 = Amount::DECIMAL_POINTS as i8;
+
+const AMOUNT_TYPE: DataType = DataType::Decimal128(AMOUNT_PRECISION, AMOUNT_SCALE);
+
+/// Schema of the account state record batches. Amounts with more than [`AMOUNT_PRECISION`]
+/// digits are null, as is the total if it overflows.
+pub fn accounts_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("client", DataType::UInt16, false),
+        Field::new("available", AMOUNT_TYPE, true),
+        Field::new("held", AMOUNT_TYPE, true),
+        Field::new("total", AMOUNT_TYPE, true),
+        Field::new("locked", DataType::Boolean, false),
+    ]))
+}
+
+/// Schema of the rejection record batches. Disputes, resolutions and charge backs
+/// have a null amount, their tx is the disputed deposit. Amounts with more than
+/// [`AMOUNT_PRECISION`] digits are null.
+pub fn rejections_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("sequence", DataType::UInt64, false),
+        Field::new("client", DataType::UInt16, false),
+        Field::new("type", DataType::Utf8, false),
+        Field::new("tx", DataType::UInt32, false),
+        Field::new("amount", AMOUNT_TYPE, true),
+        Field::new("error", DataType::Utf8, false),
+    ]))
+}
+
+/// Record batch of the account states, in the given order.
+///
+/// # Errors
+/// The columns don't match [`accounts_schema`].
+pub fn accounts_to_record_batch(states: &[AccountState]) -> Result<RecordBatch, ArrowError> {
+    let totals = states
+        .iter()
+        .map(|state| state.total().ok())
+        .collect::<Vec<_>>();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt16Array::from_iter_values(
+            states.iter().map(|state| state.id.0),
+        )),
+        amounts(states.iter().map(|state| Some(&state.available)))?,
+        amounts(states.iter().map(|state| Some(&state.held)))?,
+        amounts(totals.iter().map(Option::as_ref))?,
+        Arc::new(BooleanArray::from_iter(
+            states
+                .iter()
+                .map(|state| Some(state.is_locked == IsLocked::Locked)),
+        )),
+    ];
+
+    RecordBatch::try_new(accounts_schema(), columns)
+}
+
+/// Record batch of the rejected transactions, in the given order.
+///
+/// # Errors
+/// The columns don't match [`rejections_schema`].
+pub fn rejections_to_record_batch(rejections: &[Rejection]) -> Result<RecordBatch, ArrowError> {
+    let fields = rejections
+        .iter()
+        .map(|rejection| rejection.transaction.kind.fields())
+        .collect::<Vec<_>>();
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(
+            rejections.iter().map(|rejection| rejection.sequence),
+        )),
+        Arc::new(UInt16Array::from_iter_values(
+            rejections
+                .iter()
+                .map(|rejection| rejection.transaction.target_account_id.0),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            fields.iter().map(|(kind, _, _)| kind),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            fields.iter().map(|(_, tx_id, _)| tx_id.0),
+        )),
+        amounts(fields.iter().map(|(_, _, amount)| *amount))?,
+        Arc::new(StringArray::from_iter_values(
+            rejections
+                .iter()
+                .map(|rejection| rejection.error.to_string()),
+        )),
+    ];
+
+    RecordBatch::try_new(rejections_schema(), columns)
+}
+
+/// Write the account states into the writer as a parquet file.
+///
+/// # Errors
+/// Writing fails.
+#[cfg(feature = "parquet")]
+pub fn accounts_into_parquet(
+    writer: impl Write + Send,
+    states: &[AccountState],
+) -> Result<(), ParquetError> {
+    record_batch_into_parquet(writer, &accounts_to_record_batch(states)?)
+}
+
+/// Write the rejected transactions into the writer as a parquet file.
+///
+/// # Errors
+/// Writing fails.
+#[cfg(feature = "parquet")]
+pub fn rejections_into_parquet(
+    writer: impl Write + Send,
+    rejections: &[Rejection],
+) -> Result<(), ParquetError> {
+    record_batch_into_parquet(writer, &rejections_to_record_batch(rejections)?)
+}
+
+#[cfg(feature = "parquet")]
+fn record_batch_into_parquet(
+    writer: impl Write + Send,
+    batch: &RecordBatch,
+) -> Result<(), ParquetError> {
+    let mut writer = ArrowWriter::try_new(writer, batch.schema(), None)?;
+    writer.write(batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Decimal column of the amounts, at [`AMOUNT_PRECISION`] and [`AMOUNT_SCALE`]. Amounts
+/// with more digits than the precision are null.
+///
+/// # Errors
+/// The precision and scale are invalid.
+fn amounts<'a>(amounts: impl Iterator<Item = Option<&'a Amount>>) -> Result<ArrayRef, ArrowError> {
+    let max_mantissa = 10i128.pow(u32::from(AMOUNT_PRECISION));
+    let array = Decimal128Array::from_iter(amounts.map(|amount| {
+        let amount = amount?;
+        // amounts are below 2^96, their fixed point mantissa fits an i128
+        let mantissa = amount.to_fixed_point() as i128;
+        if mantissa >= max_mantissa {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                amount = %amount.to_fixed_string(),
+                "Amount over the decimal column precision, written as null"
+            );
+            return None;
+        }
+
+        Some(mantissa)
+    }))
+    .with_precision_and_scale(AMOUNT_PRECISION, AMOUNT_SCALE)?;
+
+    Ok(Arc::new(array))
+}
+
+#[cfg(test)]
+mod tests {
+    use account::{AccountId, Error, Transaction, TransactionId};
+    use arrow_array::Array;
+
+    use super::*;
+
+    fn states() -> Vec<AccountState> {
+        vec![
+            AccountState {
+                id: AccountId(2),
+                available: Amount::from_fixed_point(105, 1).unwrap(),
+                held: Amount::MIN,
+                is_locked: IsLocked::Unlocked,
+            },
+            AccountState {
+                id: AccountId(300),
+                available: Amount::MIN,
+                held: Amount::from_u64(3),
+                is_locked: IsLocked::Locked,
+            },
+        ]
+    }
+
+    fn decimals(batch: &RecordBatch, column: &str) -> Vec<Option<i128>> {
+        batch
+            .column_by_name(column)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Decimal128Array>()
+            .unwrap()
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn accounts_record_batch() {
+        let batch = accounts_to_record_batch(&states()).unwrap();
+
+        assert_eq!(batch.schema(), accounts_schema());
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(decimals(&batch, "available"), [Some(105_000), Some(0)]);
+        assert_eq!(decimals(&batch, "total"), [Some(105_000), Some(30_000)]);
+    }
+
+    #[test]
+    fn amounts_over_precision_are_null() {
+        let mut states = states();
+        states[0].held = Amount::from_fixed_point(10u128.pow(24), 0).unwrap();
+        states[1].available = Amount::MAX;
+
+        let batch = accounts_to_record_batch(&states).unwrap();
+
+        assert_eq!(decimals(&batch, "available"), [Some(105_000), None]);
+        assert_eq!(decimals(&batch, "held"), [None, Some(30_000)]);
+        assert_eq!(decimals(&batch, "total"), [None, None]);
+    }
+
+    fn rejections() -> [Rejection; 2] {
+        [
+            Rejection {
+                sequence: 3,
+                transaction: Transaction::withdraw(
+                    AccountId(1),
+                    TransactionId(12),
+                    Amount::from_u64(20),
+                )
+                .unwrap(),
+                error: Error::InsufficientFundsForWithdraw,
+            },
+            Rejection {
+                sequence: 10,
+                transaction: Transaction::dispute(AccountId(2), TransactionId(7)),
+                error: Error::InvalidDisputeTarget,
+            },
+        ]
+    }
+
+    #[test]
+    fn rejected_amounts_over_precision_are_null() {
+        let mut rejections = rejections();
+        rejections[0].transaction =
+            Transaction::deposit(AccountId(1), TransactionId(12), Amount::MAX).unwrap();
+
+        let batch = rejections_to_record_batch(&rejections).unwrap();
+
+        assert_eq!(decimals(&batch, "amount"), [None, None]);
+        assert_eq!(batch.column_by_name("amount").unwrap().null_count(), 2);
+    }
+
+    #[test]
+    fn rejections_record_batch() {
+        let batch = rejections_to_record_batch(&rejections()).unwrap();
+
+        assert_eq!(batch.schema(), rejections_schema());
+        assert_eq!(decimals(&batch, "amount"), [Some(200_000), None]);
+        assert_eq!(batch.column_by_name("type").unwrap().null_count(), 0);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_round_trip() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let mut output = Vec::new();
+        accounts_into_parquet(&mut output, &states()).unwrap();
+
+        let batches = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(output))
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(batches, [accounts_to_record_batch(&states()).unwrap()]);
+    }
+}
@@ -0,0 +1,160 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use async_compression::futures::{
+    bufread::{GzipDecoder, ZstdDecoder},
+    write::{GzipEncoder, ZstdEncoder},
+};
+use futures::{io::BufReader, AsyncRead, AsyncWrite};
+
+/// Compression of an input or output stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain stream.
+    #[default]
+    None,
+    /// Gzip stream. Concatenated gzip members are read as one stream.
+    Gzip,
+    /// Zstandard stream. Concatenated frames are read as one stream.
+    Zstd,
+}
+
+impl Compression {
+    /// Compression given by the extension of the path. Files with a `gz` extension are
+    /// gzip, files with a `zst` extension are zstandard, anything else is not compressed.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension() {
+            Some(extension) if extension == "gz" => Compression::Gzip,
+            Some(extension) if extension == "zst" => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Decompress the reader as it is read. Reader is buffered.
+    pub fn decoder<'a>(
+        self,
+        reader: impl AsyncRead + Unpin + Send + 'a,
+    ) -> Box<dyn AsyncRead + Unpin + Send + 'a> {
+        match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => {
+                let mut decoder = GzipDecoder::new(BufReader::new(reader));
+                decoder.multiple_members(true);
+                Box::new(decoder)
+            }
+            Compression::Zstd => {
+                let mut decoder = ZstdDecoder::new(BufReader::new(reader));
+                decoder.multiple_members(true);
+                Box::new(decoder)
+            }
+        }
+    }
+
+    /// Compress into the writer as it is written. The encoder must be closed to
+    /// end the compressed stream, flushing it is not enough.
+    pub fn encoder<'a>(
+        self,
+        writer: impl AsyncWrite + Unpin + Send + 'a,
+    ) -> Box<dyn AsyncWrite + Unpin + Send + 'a> {
+        match self {
+            Compression::None => Box::new(writer),
+            Compression::Gzip => Box::new(GzipEncoder::new(writer)),
+            Compression::Zstd => Box::new(ZstdEncoder::new(writer)),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(compression: &str) -> Result<Self, Self::Err> {
+        match compression {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(format!(
+                "Unknown compression {compression}, expected none, gzip or zstd"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{AsyncReadExt, AsyncWriteExt};
+    use test_case::test_case;
+
+    use super::*;
+
+    const CSV_RECORDS: &str = "type,client,tx,amount
+deposit,1,1,1.0
+withdraw,1,2,0.5
+";
+
+    fn compress(compression: Compression, input: &str) -> Vec<u8> {
+        crate::rt::block_on(async {
+            let mut output = Vec::new();
+            let mut encoder = compression.encoder(&mut output);
+            encoder.write_all(input.as_bytes()).await.unwrap();
+            encoder.close().await.unwrap();
+            drop(encoder);
+
+            output
+        })
+    }
+
+    fn decompress(compression: Compression, input: &[u8]) -> String {
+        crate::rt::block_on(async {
+            let mut output = String::new();
+            compression
+                .decoder(input)
+                .read_to_string(&mut output)
+                .await
+                .unwrap();
+
+            output
+        })
+    }
+
+    #[test_case(Compression::None ; "None")]
+    #[test_case(Compression::Gzip ; "Gzip")]
+    #[test_case(Compression::Zstd ; "Zstd")]
+    fn round_trip(compression: Compression) {
+        let compressed = compress(compression, CSV_RECORDS);
+
+        assert_eq!(
+            compressed == CSV_RECORDS.as_bytes(),
+            compression == Compression::None
+        );
+        assert_eq!(decompress(compression, &compressed), CSV_RECORDS);
+    }
+
+    #[test_case(Compression::Gzip ; "Gzip")]
+    #[test_case(Compression::Zstd ; "Zstd")]
+    fn concatenated_streams_are_read_as_one(compression: Compression) {
+        let mut compressed = compress(compression, CSV_RECORDS);
+        compressed.extend(compress(compression, "deposit,2,3,4.0\n"));
+
+        assert_eq!(
+            decompress(compression, &compressed),
+            format!("{CSV_RECORDS}deposit,2,3,4.0\n")
+        );
+    }
+
+    #[test_case("transactions.csv.gz" => Compression::Gzip ; "Gzip extension")]
+    #[test_case("transactions.jsonl.zst" => Compression::Zstd ; "Zstd extension")]
+    #[test_case("transactions.csv" => Compression::None ; "Csv extension")]
+    #[test_case("-" => Compression::None ; "Stdin")]
+    fn compression_from_path(path: &str) -> Compression {
+        Compression::from_path(path)
+    }
+}
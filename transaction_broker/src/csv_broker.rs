@@ -1,36 +1,101 @@
-use account::{AccountState, Transaction};
-use async_std::stream::{self, StreamExt};
+use std::{
+    collections::VecDeque,
+    io,
+    num::NonZeroUsize,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
 
-use futures::Stream;
-#[cfg(feature = "tracing")]
-use tracing;
+use account::{
+    AccountId, AccountState, AccountStateRecord, Amount, IsLocked, TimedTransaction, Transaction,
+};
+use csv_async::{AsyncReader, StringRecord};
+use futures::{stream, stream::BoxStream, AsyncRead, AsyncWrite, AsyncWriteExt, Stream, StreamExt};
+use serde::Serialize;
+
+use crate::byte_record::txs_from_reader_bytes;
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::parallel_csv::{txs_from_reader_parallel, ParallelParsing};
+use crate::rt;
+use crate::streaming::Delta;
+use crate::{ActorBroker, Backpressure, Broker, SequentialBroker, ShardedBroker};
 
 // TODO: use PATH instead of file name?
 // TODO: error handling
 // TODO: belongs to another file
-#[cfg_attr(feature = "tracing", tracing::instrument)]
+/// Process the transactions of the input csv file with an [`ActorBroker`] and write
+/// the final account states into the output csv file.
 pub async fn process_csv_txs(input_file_name: &str, output_file_name: &str) {
+    process_csv_txs_with_broker(&ActorBroker::default(), input_file_name, output_file_name).await
+}
+
+/// Process the transactions of the input csv file with the broker and write
+/// the final account states into the output csv file.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(broker)))]
+pub async fn process_csv_txs_with_broker(
+    broker: &impl Broker,
+    input_file_name: &str,
+    output_file_name: &str,
+) {
     let records = txs_from_csv(input_file_name)
         .await
         .expect("Must be able to open file");
 
-    let states = crate::transaction_broker(records).await;
+    let output = broker.process(records).await;
 
-    accounts_into_csv(output_file_name, states)
+    accounts_into_csv(output_file_name, stream::iter(output.accounts))
         .await
         .expect("Must be able to open file");
 }
 
+/// Process the transactions of the input csv file with an [`ActorBroker`] using the
+/// given backpressure.
+#[deprecated(note = "Use `process_csv_txs_with_broker` with an `ActorBroker`")]
+pub async fn process_csv_txs_with_backpressure(
+    input_file_name: &str,
+    output_file_name: &str,
+    backpressure: &Backpressure,
+) {
+    let broker = ActorBroker {
+        backpressure: backpressure.clone(),
+        ..ActorBroker::default()
+    };
+    process_csv_txs_with_broker(&broker, input_file_name, output_file_name).await
+}
+
+/// Process the transactions of the input csv file with a [`SequentialBroker`].
+#[deprecated(note = "Use `process_csv_txs_with_broker` with a `SequentialBroker`")]
 pub async fn process_csv_txs_sync(input_file_name: &str, output_file_name: &str) {
-    let records = txs_from_csv(input_file_name)
-        .await
-        .expect("Must be able to open file");
+    let broker = SequentialBroker::default();
+    process_csv_txs_with_broker(&broker, input_file_name, output_file_name).await
+}
 
-    let states = crate::transaction_broker_sync(records).await;
+/// Process the transactions of the input csv file with a [`ShardedBroker`] of the
+/// given number of workers.
+#[deprecated(note = "Use `process_csv_txs_with_broker` with a `ShardedBroker`")]
+pub async fn process_csv_txs_sharded(
+    input_file_name: &str,
+    output_file_name: &str,
+    workers: NonZeroUsize,
+) {
+    let broker = ShardedBroker {
+        workers,
+        ..ShardedBroker::default()
+    };
+    process_csv_txs_with_broker(&broker, input_file_name, output_file_name).await
+}
 
-    accounts_into_csv(output_file_name, states)
-        .await
-        .expect("Must be able to open file");
+/// Parser used to deserialize the transaction records.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CsvParser {
+    /// Records are deserialized one by one, using serde.
+    #[default]
+    Serde,
+    /// Chunks of records are deserialized in parallel, using serde.
+    Parallel(ParallelParsing),
+    /// Records are decoded one by one, directly from bytes.
+    ByteRecord,
 }
 
 /// Read a csv file and deserialize it using serde.
@@ -39,46 +104,347 @@ pub async fn process_csv_txs_sync(input_file_name: &str, output_file_name: &str)
 ///
 /// # Errors
 /// Error::FailedToOpenFile
+pub async fn txs_from_csv(input_file_name: &str) -> Result<BoxStream<'static, Transaction>, ()> {
+    txs_from_csv_with_parser(input_file_name, &CsvParser::default()).await
+}
+
+/// Read a csv file and deserialize it with the given parser.
+/// Reader is buffered. Fields are assigned based on headers.
+/// If a record can't be deserialized it is ignored.
+/// With the `compression` feature, files are decompressed as they are read,
+/// following `Compression::from_path`.
+///
+/// # Errors
+/// Error::FailedToOpenFile
 #[cfg_attr(feature = "tracing", tracing::instrument)]
-pub async fn txs_from_csv(input_file_name: &str) -> Result<impl Stream<Item = Transaction>, ()> {
-    let file = async_std::fs::File::open(input_file_name)
-        .await
-        .map_err(|_e| ())?;
-    let csv_reader = csv_async::AsyncDeserializer::from_reader(file);
+pub async fn txs_from_csv_with_parser(
+    input_file_name: &str,
+    parser: &CsvParser,
+) -> Result<BoxStream<'static, Transaction>, ()> {
+    let file = rt::open(input_file_name).await.map_err(|_e| ())?;
+    #[cfg(feature = "compression")]
+    let file = Compression::from_path(input_file_name).decoder(file);
+
+    let txs: BoxStream<_> = match parser {
+        CsvParser::Serde => Box::pin(txs_from_reader(file)),
+        CsvParser::Parallel(config) => Box::pin(txs_from_reader_parallel(file, config)),
+        CsvParser::ByteRecord => Box::pin(txs_from_reader_bytes(file)),
+    };
+
+    Ok(txs)
+}
+
+/// Deserialize csv records from the reader using serde.
+/// Fields are assigned based on headers.
+/// If a record can't be deserialized it is ignored.
+pub fn txs_from_reader(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+) -> impl Stream<Item = Transaction> {
+    let csv_reader = csv_async::AsyncDeserializer::from_reader(reader);
 
     // might be better to let the caller decide what to do with errors
-    Ok(csv_reader.into_deserialize().flat_map(|result| {
+    csv_reader.into_deserialize().flat_map(|result| {
         #[cfg(feature = "tracing")]
         if result.is_err() {
             tracing::error!(err = ?result, "Failed to deserialize record");
         }
 
-        stream::from_iter(result)
-    }))
+        stream::iter(result)
+    })
+}
+
+/// Deserialize csv records from the reader using serde, with the line number
+/// of every record and its optional `timestamp` column. Fields are assigned based on headers.
+/// If a record can't be deserialized it is ignored.
+///
+/// Records spanning many lines are numbered by their last line.
+pub fn txs_with_lines_from_reader(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+) -> impl Stream<Item = (u64, TimedTransaction)> {
+    let reader = LineReader {
+        csv_reader: AsyncReader::from_reader(LineCounter {
+            reader,
+            read: 0,
+            line_breaks: VecDeque::new(),
+            lines_passed: 0,
+        }),
+        headers: None,
+        record: StringRecord::new(),
+    };
+
+    stream::unfold(reader, |mut reader| async move {
+        let transaction = reader.next_transaction().await?;
+        Some((transaction, reader))
+    })
+}
+
+/// Reads the records one by one, keeping track of their line numbers.
+struct LineReader<R> {
+    csv_reader: AsyncReader<LineCounter<R>>,
+    /// `None` until the headers are read.
+    headers: Option<StringRecord>,
+    record: StringRecord,
+}
+
+impl<R: AsyncRead + Unpin + Send> LineReader<R> {
+    /// Next valid transaction, with its line number.
+    /// Returns `None` once the input is exhausted.
+    ///
+    /// # Errors
+    /// Invalid headers and read errors end the input.
+    async fn next_transaction(&mut self) -> Option<(u64, TimedTransaction)> {
+        if self.headers.is_none() {
+            let headers = self.csv_reader.headers().await;
+            #[cfg(feature = "tracing")]
+            if headers.is_err() {
+                tracing::error!(err = ?headers, "Failed to read headers");
+            }
+            self.headers = Some(headers.ok()?.clone());
+        }
+
+        loop {
+            let result = self.csv_reader.read_record(&mut self.record).await;
+            #[cfg(feature = "tracing")]
+            if result.is_err() {
+                tracing::error!(err = ?result, "Failed to read record");
+            }
+            match result {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(_e) => continue,
+            }
+
+            // the csv positions count the blank lines before a record, and count a
+            // `\r\n` break in the next record. The last byte read is the line break
+            // ending the record, or its last byte at the end of the input.
+            let last_byte = self.csv_reader.position().byte().saturating_sub(1);
+            let line = self.csv_reader.get_mut().line_of(last_byte);
+
+            let result = self.record.deserialize(self.headers.as_ref());
+            #[cfg(feature = "tracing")]
+            if result.is_err() {
+                tracing::error!(err = ?result, line, "Failed to deserialize record");
+            }
+            if let Ok(transaction) = result {
+                return Some((line, transaction));
+            }
+        }
+    }
+}
+
+/// Reader counting the line breaks of the bytes read.
+struct LineCounter<R> {
+    reader: R,
+    /// Number of bytes read.
+    read: u64,
+    /// Offsets of the line breaks read, that were not passed yet.
+    line_breaks: VecDeque<u64>,
+    /// Number of line breaks passed.
+    lines_passed: u64,
+}
+
+impl<R> LineCounter<R> {
+    /// Line number of the byte at the given offset, starting at 1. Offsets must not
+    /// decrease from one call to the next.
+    fn line_of(&mut self, offset: u64) -> u64 {
+        while self
+            .line_breaks
+            .front()
+            .is_some_and(|line_break| *line_break < offset)
+        {
+            self.line_breaks.pop_front();
+            self.lines_passed += 1;
+        }
+
+        self.lines_passed + 1
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for LineCounter<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let len = ready!(Pin::new(&mut self.reader).poll_read(cx, buf))?;
+        let read = self.read;
+        self.line_breaks.extend(
+            buf[..len]
+                .iter()
+                .enumerate()
+                .filter(|(_, byte)| **byte == b'\n')
+                .map(|(position, _)| read + position as u64),
+        );
+        self.read += len as u64;
+
+        Poll::Ready(Ok(len))
+    }
 }
 
 /// Write the account states to a CSV file.
 /// Serialization is done using serde.
 /// Serialization errors are ignored.
+/// With the `compression` feature, files are compressed as they are written,
+/// following `Compression::from_path`.
 #[cfg_attr(feature = "tracing", tracing::instrument(skip(account_states)))]
 pub async fn accounts_into_csv(
     output_file_name: &str,
-    mut account_states: impl Stream<Item = AccountState> + Unpin,
+    account_states: impl Stream<Item = AccountState> + Unpin,
 ) -> Result<(), ()> {
-    let dst_file = async_std::fs::File::create(output_file_name)
-        .await
-        .map_err(|_e| ())?;
-    let mut wtr = csv_async::AsyncSerializer::from_writer(dst_file);
+    let dst_file = rt::create(output_file_name).await.map_err(|_e| ())?;
+    #[cfg(feature = "compression")]
+    let dst_file = Compression::from_path(output_file_name).encoder(dst_file);
+    let mut dst_file = dst_file;
+
+    accounts_into_writer(&mut dst_file, account_states).await;
+
+    // compressed streams end when closed
+    dst_file.close().await.map_err(|_e| ())
+}
+
+/// Write the account states as CSV records into the writer.
+/// Account states are written in the order they are received.
+/// Serialization errors are ignored.
+pub async fn accounts_into_writer(
+    writer: impl AsyncWrite + Unpin,
+    mut account_states: impl Stream<Item = AccountState> + Unpin,
+) {
+    let mut wtr = csv_async::AsyncSerializer::from_writer(writer);
 
-    // TODO: https://docs.rs/tabwriter/1.2.1/tabwriter/
-    // feature gate pretty print
     while let Some(state) = account_states.next().await {
-        let _res = wtr.serialize(state).await;
+        let _res = match AccountStateRecord::try_from(state) {
+            Ok(record) => wtr.serialize(record).await.map_err(|_e| ()),
+            Err(_e) => Err(()),
+        };
         #[cfg(feature = "tracing")]
         if _res.is_err() {
             tracing::error!(err = ?_res, "Failed to serialize record");
         }
     }
 
+    let _res = wtr.flush().await;
+}
+
+/// Write the deltas as CSV records into the writer. Every account state of a delta
+/// is a record, prefixed by the delta sequence number.
+/// The writer is flushed after every delta, so consumers see the complete delta.
+/// Serialization errors are ignored.
+///
+/// # Errors
+/// Flushing the writer fails.
+pub async fn deltas_into_writer(
+    writer: impl AsyncWrite + Unpin,
+    mut deltas: impl Stream<Item = Delta> + Unpin,
+) -> io::Result<()> {
+    let mut wtr = csv_async::AsyncSerializer::from_writer(writer);
+
+    while let Some(delta) = deltas.next().await {
+        for state in delta.accounts {
+            let _res = match DeltaRecord::new(delta.sequence, state) {
+                Ok(record) => wtr.serialize(record).await.map_err(|_e| ()),
+                Err(_e) => Err(()),
+            };
+            #[cfg(feature = "tracing")]
+            if _res.is_err() {
+                tracing::error!(err = ?_res, "Failed to serialize record");
+            }
+        }
+
+        wtr.flush().await?;
+    }
+
     Ok(())
 }
+
+/// Output representation of an account state in a delta.
+#[derive(Serialize)]
+struct DeltaRecord {
+    sequence: u64,
+    client: AccountId,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: IsLocked,
+}
+
+impl DeltaRecord {
+    fn new(sequence: u64, state: AccountState) -> Result<Self, account::Error> {
+        Ok(DeltaRecord {
+            sequence,
+            total: state.total()?,
+            client: state.id,
+            available: state.available,
+            held: state.held,
+            locked: state.is_locked,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use account::{AccountId, Amount, TransactionId};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::rt;
+    use crate::{AccountOrder, ActorBroker, BrokerOutput, SequentialBroker, ShardedBroker};
+
+    /// Deposits and withdrawals for many clients, with client ids appearing out of order.
+    fn scrambled_txs() -> Vec<Transaction> {
+        (0..1000u32)
+            .map(|tx_id| {
+                let account_id = AccountId(((tx_id * 7919) % 97) as u16);
+                if tx_id % 3 == 0 {
+                    Transaction::withdraw(account_id, TransactionId(tx_id), Amount::from_u64(1))
+                } else {
+                    Transaction::deposit(account_id, TransactionId(tx_id), Amount::from_u64(2))
+                }
+                .unwrap()
+            })
+            .collect()
+    }
+
+    async fn csv_output(broker: &impl Broker) -> Vec<u8> {
+        let broker_output: BrokerOutput = broker.process(stream::iter(scrambled_txs())).await;
+
+        let mut output = Vec::new();
+        accounts_into_writer(&mut output, stream::iter(broker_output.accounts)).await;
+
+        output
+    }
+
+    #[test]
+    fn brokers_produce_identical_output() {
+        rt::block_on(async {
+            for order in [AccountOrder::ClientId, AccountOrder::FirstSeen] {
+                let sync_output = csv_output(&SequentialBroker { order }).await;
+
+                for _ in 0..5 {
+                    let actor_broker = ActorBroker {
+                        order,
+                        ..ActorBroker::default()
+                    };
+                    let sharded_broker = ShardedBroker {
+                        order,
+                        ..ShardedBroker::default()
+                    };
+
+                    assert_eq!(csv_output(&actor_broker).await, sync_output);
+                    assert_eq!(csv_output(&sharded_broker).await, sync_output);
+                }
+            }
+        })
+    }
+
+    #[test_case("type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,1.0\n" => vec![2, 3] ; "Consecutive lines")]
+    #[test_case("type,client,tx,amount\r\ndeposit,1,1,1.0\r\n\r\n\r\ndeposit,1,2,1.0\r\n" => vec![2, 5] ; "Blank lines")]
+    #[test_case("type,client,tx,amount\ndeposit,1,1,1.0\nnot,a,record\ndeposit,1,2,1.0" => vec![2, 4] ; "Invalid record and no final line break")]
+    fn record_lines(input: &'static str) -> Vec<u64> {
+        rt::block_on(async {
+            txs_with_lines_from_reader(futures::io::Cursor::new(input))
+                .map(|(line, _transaction)| line)
+                .collect()
+                .await
+        })
+    }
+}
@@ -0,0 +1,319 @@
+//! Differential tests. Both brokers are run over the same random, multi account,
+//! transaction streams and compared with each other and with a reference model.
+
+use std::collections::BTreeMap;
+use std::num::{NonZeroU64, NonZeroUsize};
+
+use account::{AccountId, Ledger, Transaction};
+use futures::{future, stream, StreamExt};
+use proptest::{collection::vec, prelude::*};
+use test_utils::{pattern_iter, Interleave, TransactionRequestCompressed};
+
+use crate::rt;
+use crate::{
+    AccountOrder, ActorBroker, Broker, BrokerOutput, Emission, SequencedEvent, SequentialBroker,
+    ShardedBroker, StreamingBroker,
+};
+
+/// Straightforward implementation of the specification. Shares nothing with
+/// the `Account` implementation except the amount arithmetic.
+mod model {
+    use std::collections::{BTreeMap, HashMap};
+
+    use account::TransactionKind::{ChargeBack, Deposit, Dispute, Resolve, Withdraw};
+    use account::{AccountId, AccountState, Amount, IsLocked, Transaction, TransactionId};
+
+    #[derive(PartialEq)]
+    enum DepositState {
+        Settled,
+        Disputed,
+        ChargedBack,
+    }
+
+    struct ModelAccount {
+        available: Amount,
+        held: Amount,
+        locked: bool,
+        deposits: HashMap<TransactionId, (Amount, DepositState)>,
+    }
+
+    impl ModelAccount {
+        fn new() -> Self {
+            ModelAccount {
+                available: Amount::MIN,
+                held: Amount::MIN,
+                locked: false,
+                deposits: HashMap::new(),
+            }
+        }
+
+        /// Apply the transaction. Returns `None` if the transaction is rejected,
+        /// in which case the account is left untouched.
+        fn apply(&mut self, transaction: &Transaction) -> Option<()> {
+            if self.locked {
+                return None;
+            }
+
+            match &transaction.kind {
+                Deposit(deposit) => {
+                    self.available = self.available.checked_add(deposit.amount())?;
+                    self.deposits.insert(
+                        deposit.to_tx_id(),
+                        (deposit.to_amount(), DepositState::Settled),
+                    );
+                }
+                Withdraw(withdraw) => {
+                    self.available = self.available.checked_sub(withdraw.amount())?;
+                }
+                Dispute(dispute) => {
+                    let (amount, state) = self.deposits.get_mut(&dispute.target_tx_id)?;
+                    if *state != DepositState::Settled {
+                        return None;
+                    }
+                    let available = self.available.checked_sub(amount)?;
+                    let held = self.held.checked_add(amount)?;
+
+                    self.available = available;
+                    self.held = held;
+                    *state = DepositState::Disputed;
+                }
+                Resolve(resolve) => {
+                    let (amount, state) = self.deposits.get_mut(&resolve.target_tx_id)?;
+                    if *state != DepositState::Disputed {
+                        return None;
+                    }
+                    let available = self.available.checked_add(amount)?;
+                    let held = self.held.checked_sub(amount)?;
+
+                    self.available = available;
+                    self.held = held;
+                    *state = DepositState::Settled;
+                }
+                ChargeBack(charge_back) => {
+                    let (amount, state) = self.deposits.get_mut(&charge_back.target_tx_id)?;
+                    if *state != DepositState::Disputed {
+                        return None;
+                    }
+
+                    self.held = self.held.checked_sub(amount)?;
+                    self.locked = true;
+                    *state = DepositState::ChargedBack;
+                }
+            }
+
+            Some(())
+        }
+    }
+
+    /// Run the model over the transactions. Returns the account states ordered
+    /// by client id and the sequence numbers of the rejected transactions.
+    pub fn run(transactions: &[Transaction]) -> (Vec<AccountState>, Vec<u64>) {
+        let mut accounts = BTreeMap::new();
+        let mut rejected = Vec::new();
+
+        for (sequence, transaction) in transactions.iter().enumerate() {
+            let account = accounts
+                .entry(transaction.target_account_id.clone())
+                .or_insert_with(ModelAccount::new);
+
+            if account.apply(transaction).is_none() {
+                rejected.push(sequence as u64);
+            }
+        }
+
+        let states = accounts
+            .into_iter()
+            .map(|(id, account): (AccountId, ModelAccount)| AccountState {
+                id,
+                available: account.available,
+                held: account.held,
+                is_locked: if account.locked {
+                    IsLocked::Locked
+                } else {
+                    IsLocked::Unlocked
+                },
+            })
+            .collect();
+
+        (states, rejected)
+    }
+}
+
+fn compressed_tx(pattern_len: u32) -> impl Strategy<Value = TransactionRequestCompressed> {
+    prop_oneof![
+        3 => (1..100u64).prop_map(TransactionRequestCompressed::Deposit),
+        2 => (1..100u64).prop_map(TransactionRequestCompressed::Withdraw),
+        2 => (0..pattern_len).prop_map(TransactionRequestCompressed::Dispute),
+        1 => (0..pattern_len).prop_map(TransactionRequestCompressed::Resolve),
+        1 => (0..pattern_len).prop_map(TransactionRequestCompressed::ChargeBack),
+    ]
+}
+
+/// Transaction pattern of a single account, and the number of times it is repeated.
+fn account_pattern() -> impl Strategy<Value = (Vec<TransactionRequestCompressed>, u32)> {
+    (1..6u32).prop_flat_map(|len| (vec(compressed_tx(len), len as usize), 1..4u32))
+}
+
+/// Randomly interleaved transactions of up to 8 accounts.
+fn transactions() -> impl Strategy<Value = Vec<Transaction>> {
+    (vec(account_pattern(), 1..8), vec(any::<usize>(), 0..64)).prop_map(|(patterns, schedule)| {
+        let accounts = patterns
+            .into_iter()
+            .enumerate()
+            .map(|(index, (pattern, num_of_cycles))| {
+                // spread the ids so client id order differs from first seen order
+                let account_id = AccountId((index * 7919 % 65536) as u16);
+                pattern_iter(account_id, pattern, num_of_cycles)
+            })
+            .collect();
+
+        Interleave::new(accounts, schedule.into_iter()).collect()
+    })
+}
+
+fn run(broker: impl Broker, transactions: &[Transaction]) -> BrokerOutput {
+    rt::block_on(broker.process(stream::iter(transactions.to_vec())))
+}
+
+/// Events of the broker, ordered by their sequence number, and the broker output.
+fn run_with_events(
+    broker: impl Broker,
+    transactions: &[Transaction],
+) -> (Vec<SequencedEvent>, BrokerOutput) {
+    let (events, output) = broker.process_with_events(stream::iter(transactions.to_vec()));
+    let (mut events, output) = rt::block_on(future::join(events.collect::<Vec<_>>(), output));
+
+    // events of an account are ordered, the sort is stable
+    events.sort_by_key(|event| event.sequence);
+    (events, output)
+}
+
+/// Outputs of the sequential, actor and sharded broker.
+fn run_brokers(transactions: &[Transaction], order: AccountOrder) -> [BrokerOutput; 3] {
+    let actor_broker = ActorBroker {
+        order,
+        ..ActorBroker::default()
+    };
+    let sharded_broker = ShardedBroker {
+        order,
+        workers: NonZeroUsize::new(3).unwrap(),
+        channel_capacity: None,
+    };
+
+    [
+        run(SequentialBroker { order }, transactions),
+        run(actor_broker, transactions),
+        run(sharded_broker, transactions),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn brokers_agree(transactions in transactions()) {
+        for order in [AccountOrder::ClientId, AccountOrder::FirstSeen] {
+            let [sync_output, async_output, sharded_output] = run_brokers(&transactions, order);
+
+            prop_assert_eq!(&async_output, &sync_output);
+            prop_assert_eq!(&sharded_output, &sync_output);
+        }
+    }
+
+    #[test]
+    fn brokers_match_reference_model(transactions in transactions()) {
+        let (expected_accounts, expected_rejections) = model::run(&transactions);
+
+        for output in run_brokers(&transactions, AccountOrder::ClientId) {
+            let rejections = output
+                .rejections
+                .iter()
+                .map(|rejection| rejection.sequence)
+                .collect::<Vec<_>>();
+
+            prop_assert_eq!(&output.accounts, &expected_accounts);
+            prop_assert_eq!(&rejections, &expected_rejections);
+        }
+    }
+
+    #[test]
+    fn deltas_mirror_accounts(transactions in transactions(), every in 1..10u64) {
+        let broker = StreamingBroker {
+            emission: Emission {
+                transactions: NonZeroU64::new(every),
+                interval: None,
+            },
+            ..StreamingBroker::default()
+        };
+        let deltas = broker.deltas(stream::iter(transactions.clone()));
+
+        let mut mirror = BTreeMap::new();
+        let mut sequence = 0;
+        for delta in rt::block_on(deltas.collect::<Vec<_>>()) {
+            prop_assert!(delta.sequence > sequence && delta.sequence <= transactions.len() as u64);
+            sequence = delta.sequence;
+            for state in delta.accounts {
+                mirror.insert(state.id.clone(), state);
+            }
+        }
+
+        let output = run(SequentialBroker::default(), &transactions);
+        prop_assert_eq!(mirror.into_values().collect::<Vec<_>>(), output.accounts);
+    }
+
+    #[test]
+    fn brokers_emit_the_same_events(transactions in transactions()) {
+        let sharded_broker = ShardedBroker {
+            workers: NonZeroUsize::new(3).unwrap(),
+            channel_capacity: Some(2),
+            ..ShardedBroker::default()
+        };
+        let (sync_events, sync_output) = run_with_events(SequentialBroker::default(), &transactions);
+        let (async_events, async_output) = run_with_events(ActorBroker::default(), &transactions);
+        let (sharded_events, sharded_output) = run_with_events(sharded_broker, &transactions);
+
+        prop_assert_eq!(&async_events, &sync_events);
+        prop_assert_eq!(&sharded_events, &sync_events);
+        prop_assert_eq!(&async_output, &sync_output);
+        prop_assert_eq!(&sharded_output, &sync_output);
+
+        // every applied transaction has an event, rejected ones have none
+        let rejected = sync_output.rejections.iter().map(|rejection| rejection.sequence);
+        let mut sequences = sync_events.iter().map(|event| event.sequence).collect::<Vec<_>>();
+        sequences.extend(rejected);
+        sequences.sort_unstable();
+        sequences.dedup();
+        prop_assert_eq!(sequences, (0..transactions.len() as u64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ledger_balances_the_broker_output(transactions in transactions()) {
+        let (events, output) = run_with_events(ActorBroker::default(), &transactions);
+
+        let mut ledger = Ledger::default();
+        for event in &events {
+            prop_assert_eq!(ledger.post(&event.event), Ok(()));
+        }
+
+        prop_assert_eq!(ledger.trial_balance(&output.accounts), Ok(()));
+    }
+
+    #[test]
+    fn tallies_count_every_transaction(transactions in transactions()) {
+        let output = run(ActorBroker::default(), &transactions);
+        let tallies = &output.tallies;
+        let types = [
+            tallies.deposits,
+            tallies.withdrawals,
+            tallies.disputes,
+            tallies.resolves,
+            tallies.chargebacks,
+        ];
+
+        let applied = types.iter().map(|tally| tally.applied.count).sum::<u64>();
+        let rejected = types.iter().map(|tally| tally.rejected.count).sum::<u64>();
+        let by_error = tallies.errors.values().map(|tally| tally.count).sum::<u64>();
+
+        prop_assert_eq!(applied + rejected, transactions.len() as u64);
+        prop_assert_eq!(rejected, output.rejections.len() as u64);
+        prop_assert_eq!(by_error, rejected);
+    }
+}
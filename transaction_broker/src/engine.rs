@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use account::{Account, AccountEvents, AccountId, AccountState, Error, Transaction};
+
+use crate::broker::AccountOrder;
+use crate::history::History;
+
+/// Blocking transaction engine. Transactions are applied to the accounts they target
+/// as soon as they are received.
+#[derive(Debug, Default)]
+pub struct Engine {
+    order: AccountOrder,
+    accounts: HashMap<AccountId, Account>,
+    /// Account ids, in the order they were first seen.
+    first_seen: Vec<AccountId>,
+    /// Recorded transactions, if the history is kept.
+    history: Option<History>,
+}
+
+impl Engine {
+    /// Engine emitting the final account states in the given order.
+    pub fn new(order: AccountOrder) -> Self {
+        Engine {
+            order,
+            ..Engine::default()
+        }
+    }
+
+    /// Keep the history of every applied and rejected transaction.
+    pub fn with_history(mut self) -> Self {
+        self.history = Some(History::default());
+        self
+    }
+
+    /// Apply the transaction to its target account, returning the events of the change.
+    /// The account is opened by its first transaction.
+    ///
+    /// # Errors
+    /// Transactions that can't be applied leave the account unchanged.
+    pub fn apply(&mut self, transaction: Transaction) -> Result<AccountEvents, Error> {
+        let recorded = self.history.is_some().then(|| transaction.clone());
+        let account = self
+            .accounts
+            .entry(transaction.target_account_id.clone())
+            .or_insert_with_key(|account_id| {
+                self.first_seen.push(account_id.clone());
+                Account::from_id(account_id.clone())
+            });
+
+        let result = account.try_apply_transaction(transaction);
+
+        if let (Some(history), Some(transaction)) = (&mut self.history, recorded) {
+            history.record(transaction, result.as_ref().err().cloned(), account.state());
+        }
+
+        result
+    }
+
+    /// Recorded transactions. `None` if the history is not kept.
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Current state of the account. `None` if the account has not been seen.
+    pub fn account(&self, account_id: &AccountId) -> Option<&AccountState> {
+        self.accounts.get(account_id).map(Account::state)
+    }
+
+    /// Final account states, in the engine's account order.
+    pub fn into_states(mut self) -> Vec<AccountState> {
+        self.order
+            .arrange(self.first_seen)
+            .into_iter()
+            .map(|account_id| {
+                self.accounts
+                    .remove(&account_id)
+                    .expect("Every seen account must be present")
+                    .into_state()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::{Bound, RangeBounds};
+
+    use account::{Amount, IsLocked, TransactionId};
+    use test_case::test_case;
+
+    use super::*;
+
+    fn deposit(account_id: u16, tx_id: u32, amount: u64) -> Transaction {
+        Transaction::deposit(
+            AccountId(account_id),
+            TransactionId(tx_id),
+            Amount::from_u64(amount),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rejected_transactions_leave_the_account_unchanged() {
+        let mut engine = Engine::default();
+
+        engine.apply(deposit(1, 0, 10)).unwrap();
+        let err = engine
+            .apply(
+                Transaction::withdraw(AccountId(1), TransactionId(1), Amount::from_u64(20))
+                    .unwrap(),
+            )
+            .unwrap_err();
+
+        assert_eq!(err, Error::InsufficientFundsForWithdraw);
+        assert_eq!(
+            engine.account(&AccountId(1)),
+            Some(&AccountState {
+                id: AccountId(1),
+                available: Amount::from_u64(10),
+                held: Amount::MIN,
+                is_locked: IsLocked::Unlocked,
+            })
+        );
+        assert_eq!(engine.account(&AccountId(2)), None);
+    }
+
+    #[test]
+    fn states_are_arranged_by_account_order() {
+        let input = [5, 3, 9, 3, 1, 5, 0];
+
+        for (order, expected) in [
+            (AccountOrder::ClientId, vec![0, 1, 3, 5, 9]),
+            (AccountOrder::FirstSeen, vec![5, 3, 9, 1, 0]),
+        ] {
+            let mut engine = Engine::new(order);
+            for (tx_id, account_id) in input.into_iter().enumerate() {
+                engine.apply(deposit(account_id, tx_id as u32, 1)).unwrap();
+            }
+
+            let ids = engine
+                .into_states()
+                .into_iter()
+                .map(|state| state.id.0)
+                .collect::<Vec<_>>();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    /// Engine with the history of deposits into accounts 1 and 2, and a rejected
+    /// withdraw from account 1.
+    fn engine_with_history() -> Engine {
+        let mut engine = Engine::default().with_history();
+        let withdraw =
+            Transaction::withdraw(AccountId(1), TransactionId(3), Amount::from_u64(20)).unwrap();
+
+        for transaction in [
+            deposit(1, 0, 10),
+            deposit(2, 1, 5),
+            deposit(1, 2, 5),
+            withdraw,
+        ] {
+            let _res = engine.apply(transaction);
+        }
+
+        engine
+    }
+
+    #[test]
+    fn history_records_running_balances() {
+        let engine = engine_with_history();
+
+        let statement = engine
+            .history()
+            .unwrap()
+            .statement(&AccountId(1), ..)
+            .iter()
+            .map(|entry| {
+                (
+                    entry.sequence,
+                    entry.state.available.clone(),
+                    entry.error.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            statement,
+            [
+                (0, Amount::from_u64(10), None),
+                (2, Amount::from_u64(15), None),
+                (
+                    3,
+                    Amount::from_u64(15),
+                    Some(Error::InsufficientFundsForWithdraw)
+                ),
+            ]
+        );
+    }
+
+    #[test_case(1..3 => vec![2] ; "Excluded end")]
+    #[test_case(1..=3 => vec![2, 3] ; "Included end")]
+    #[test_case((Bound::Included(3), Bound::Unbounded) => vec![3] ; "Unbounded end")]
+    #[test_case(..1 => vec![0] ; "Unbounded start")]
+    #[test_case((Bound::Excluded(3), Bound::Unbounded) => Vec::<u64>::new() ; "Excluded start")]
+    fn statement_between_sequence_numbers(sequences: impl RangeBounds<u64>) -> Vec<u64> {
+        let engine = engine_with_history();
+
+        engine
+            .history()
+            .unwrap()
+            .statement(&AccountId(1), sequences)
+            .iter()
+            .map(|entry| entry.sequence)
+            .collect()
+    }
+
+    #[test]
+    fn history_is_not_kept_by_default() {
+        let mut engine = Engine::default();
+        engine.apply(deposit(1, 0, 10)).unwrap();
+
+        assert!(engine.history().is_none());
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    ops::{Bound, RangeBounds},
+};
+
+use account::{AccountId, AccountState, Error, Transaction};
+
+/// Transaction recorded in the history of its target account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Position of the transaction in the input stream.
+    pub sequence: u64,
+    pub transaction: Transaction,
+    /// Reason the transaction was rejected. `None` if it was applied.
+    pub error: Option<Error>,
+    /// Account state after the transaction.
+    pub state: AccountState,
+}
+
+/// Every applied and rejected transaction, by target account.
+///
+/// The history grows with the input, it is meant for producing client statements.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    accounts: HashMap<AccountId, Vec<HistoryEntry>>,
+    /// Sequence number of the next recorded transaction.
+    sequence: u64,
+}
+
+impl History {
+    /// Record the next transaction of the input stream.
+    pub(crate) fn record(
+        &mut self,
+        transaction: Transaction,
+        error: Option<Error>,
+        state: &AccountState,
+    ) {
+        let entry = HistoryEntry {
+            sequence: self.sequence,
+            transaction,
+            error,
+            state: state.clone(),
+        };
+
+        self.accounts
+            .entry(state.id.clone())
+            .or_default()
+            .push(entry);
+        self.sequence += 1;
+    }
+
+    /// Transactions of the account with sequence numbers in the given range,
+    /// ordered by their sequence number.
+    pub fn statement(
+        &self,
+        account_id: &AccountId,
+        sequences: impl RangeBounds<u64>,
+    ) -> &[HistoryEntry] {
+        let Some(entries) = self.accounts.get(account_id) else {
+            return &[];
+        };
+
+        // entries are recorded in sequence order
+        let start = entries.partition_point(|entry| match sequences.start_bound() {
+            Bound::Included(start) => entry.sequence < *start,
+            Bound::Excluded(start) => entry.sequence <= *start,
+            Bound::Unbounded => false,
+        });
+        let end = entries.partition_point(|entry| match sequences.end_bound() {
+            Bound::Included(end) => entry.sequence <= *end,
+            Bound::Excluded(end) => entry.sequence < *end,
+            Bound::Unbounded => true,
+        });
+
+        &entries[start..end.max(start)]
+    }
+
+    /// Ids of the accounts with recorded transactions, in ascending order.
+    pub fn account_ids(&self) -> Vec<AccountId> {
+        let mut account_ids = self.accounts.keys().cloned().collect::<Vec<_>>();
+        account_ids.sort_unstable();
+        account_ids
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    str::FromStr,
+};
+
+#[cfg(feature = "async")]
+use account::TimedTransaction;
+use account::Transaction;
+#[cfg(feature = "async")]
+use futures::{future, stream, stream::BoxStream, AsyncBufReadExt, AsyncRead, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+
+use crate::blocking_csv::txs_from_csv_reader;
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+#[cfg(feature = "async")]
+use crate::csv_broker::{txs_from_reader, txs_with_lines_from_reader};
+
+/// Format of a transactions input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Csv records with a header line.
+    #[default]
+    Csv,
+    /// Newline delimited json objects, with the fields of the csv records.
+    Jsonl,
+}
+
+impl InputFormat {
+    /// Format given by the extension of the path. Files with a `jsonl` or `ndjson`
+    /// extension are json lines, anything else is csv. With the `compression` feature,
+    /// the extension of compressed files is the one before the compression extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        #[cfg(feature = "compression")]
+        let path = match Compression::from_path(path) {
+            Compression::None => path,
+            _ => Path::new(path.file_stem().unwrap_or_default()),
+        };
+
+        match path.extension() {
+            Some(extension) if extension == "jsonl" || extension == "ndjson" => InputFormat::Jsonl,
+            _ => InputFormat::Csv,
+        }
+    }
+
+    /// Deserialize the transactions of the reader using serde.
+    /// If a record can't be deserialized it is ignored.
+    pub fn txs_from_reader_blocking(
+        self,
+        reader: impl Read + 'static,
+    ) -> Box<dyn Iterator<Item = Transaction>> {
+        match self {
+            InputFormat::Csv => Box::new(txs_from_csv_reader(reader)),
+            InputFormat::Jsonl => Box::new(txs_from_jsonl_reader_blocking(reader)),
+        }
+    }
+
+    /// Deserialize the transactions of the reader using serde.
+    /// If a record can't be deserialized it is ignored.
+    #[cfg(feature = "async")]
+    pub fn txs_from_reader(
+        self,
+        reader: impl AsyncRead + Unpin + Send + 'static,
+    ) -> BoxStream<'static, Transaction> {
+        match self {
+            InputFormat::Csv => Box::pin(txs_from_reader(reader)),
+            InputFormat::Jsonl => Box::pin(txs_from_jsonl_reader(reader)),
+        }
+    }
+
+    /// Deserialize the transactions of the reader using serde, with the line number
+    /// and the optional timestamp of every record. If a record can't be deserialized
+    /// it is ignored.
+    #[cfg(feature = "async")]
+    pub fn txs_with_lines_from_reader(
+        self,
+        reader: impl AsyncRead + Unpin + Send + 'static,
+    ) -> BoxStream<'static, (u64, TimedTransaction)> {
+        match self {
+            InputFormat::Csv => Box::pin(txs_with_lines_from_reader(reader)),
+            InputFormat::Jsonl => Box::pin(txs_with_lines_from_jsonl_reader(reader)),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::Jsonl),
+            _ => Err(format!(
+                "Unknown input format {format}, expected csv or jsonl"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputFormat::Csv => write!(f, "csv"),
+            InputFormat::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+/// Deserialize newline delimited json records from the reader using serde.
+/// Every line holds a record with the `type`, `client`, `tx` and `amount` fields.
+/// Blank lines are skipped. If a record can't be deserialized it is ignored.
+///
+/// # Errors
+/// The input ends at the first read error.
+#[cfg(feature = "async")]
+pub fn txs_from_jsonl_reader(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+) -> impl Stream<Item = Transaction> {
+    futures::io::BufReader::new(reader)
+        .lines()
+        .take_while(|line| {
+            #[cfg(feature = "tracing")]
+            if line.is_err() {
+                tracing::error!(err = ?line, "Failed to read line");
+            }
+
+            future::ready(line.is_ok())
+        })
+        .flat_map(|line| stream::iter(line.ok().as_deref().and_then(parse_line)))
+}
+
+/// Deserialize newline delimited json records from the reader using serde, with the
+/// line number of every record and its optional `timestamp` field. Blank lines are skipped.
+/// If a record can't be deserialized it is ignored.
+///
+/// # Errors
+/// The input ends at the first read error.
+#[cfg(feature = "async")]
+pub fn txs_with_lines_from_jsonl_reader(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+) -> impl Stream<Item = (u64, TimedTransaction)> {
+    futures::io::BufReader::new(reader)
+        .lines()
+        .take_while(|line| {
+            #[cfg(feature = "tracing")]
+            if line.is_err() {
+                tracing::error!(err = ?line, "Failed to read line");
+            }
+
+            future::ready(line.is_ok())
+        })
+        .zip(stream::iter(1..))
+        .flat_map(|(line, number)| {
+            let transaction = line.ok().as_deref().and_then(parse_line);
+            stream::iter(transaction.map(|transaction| (number, transaction)))
+        })
+}
+
+/// Deserialize newline delimited json records from the reader using serde.
+/// Reader is buffered. Blank lines are skipped.
+/// If a record can't be deserialized it is ignored.
+///
+/// # Errors
+/// The input ends at the first read error.
+pub fn txs_from_jsonl_reader_blocking(reader: impl Read) -> impl Iterator<Item = Transaction> {
+    BufReader::new(reader)
+        .lines()
+        .map_while(|line| {
+            #[cfg(feature = "tracing")]
+            if line.is_err() {
+                tracing::error!(err = ?line, "Failed to read line");
+            }
+
+            line.ok()
+        })
+        .filter_map(|line| parse_line(&line))
+}
+
+/// Deserialize a single json record. Records are validated by the serde representation
+/// of the transaction, shared with the csv records.
+fn parse_line<T: DeserializeOwned>(line: &str) -> Option<T> {
+    if line.trim().is_empty() {
+        return None;
+    }
+
+    let result = serde_json::from_str(line);
+
+    #[cfg(feature = "tracing")]
+    if result.is_err() {
+        tracing::error!(err = ?result.as_ref().err(), "Failed to deserialize record");
+    }
+
+    result.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const CSV_RECORDS: &str = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.5
+withdraw,1,3,0.5
+dispute,2,2,
+resolve,2,2,
+deposit,3,4,10.12345
+chargeback,2,2,";
+
+    const JSONL_RECORDS: &str = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+{"type": "deposit", "client": 2, "tx": 2, "amount": 2.5}
+{"tx": 3, "client": 1, "type": "withdraw", "amount": "0.5"}
+{"type": "dispute", "client": 2, "tx": 2}
+
+{"type": "resolve", "client": 2, "tx": 2, "amount": null}
+{"type": "deposit", "client": 3, "tx": 4, "amount": "10.12345"}
+{"type": "chargeback", "client": 2, "tx": 2}"#;
+
+    const INVALID_JSONL_RECORDS: &str = r#"not a record
+{"type": "deposit", "client": 1, "tx": 1}
+{"type": "deposit", "client": 1, "tx": 3, "amount": "-1.0"}
+{"type": "deposit", "client": 1, "tx": 5, "amount": "0"}
+{"type": "withdrawal", "client": 1, "tx": 6, "amount": "1.0"}
+{"type": "deposit", "client": 65536, "tx": 7, "amount": "1.0"}
+{"type": "deposit", "client": 1, "tx": 8, "amount": "1.0"
+{"type": "deposit", "client": 1, "tx": 9, "amount": "1.0"}"#;
+
+    #[test]
+    fn jsonl_records_match_csv_records() {
+        let csv = txs_from_csv_reader(CSV_RECORDS.as_bytes()).collect::<Vec<_>>();
+        let jsonl = txs_from_jsonl_reader_blocking(JSONL_RECORDS.as_bytes()).collect::<Vec<_>>();
+
+        assert_eq!(jsonl.len(), 7);
+        assert_eq!(jsonl, csv);
+    }
+
+    #[test]
+    fn invalid_jsonl_records_are_ignored() {
+        let jsonl = txs_from_jsonl_reader_blocking(INVALID_JSONL_RECORDS.as_bytes())
+            .map(|transaction| transaction.target_account_id.0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(jsonl, [1]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_jsonl_reader_matches_blocking_reader() {
+        use futures::io::Cursor;
+
+        crate::rt::block_on(async {
+            let blocking =
+                txs_from_jsonl_reader_blocking(JSONL_RECORDS.as_bytes()).collect::<Vec<_>>();
+            let txs = txs_from_jsonl_reader(Cursor::new(JSONL_RECORDS.to_owned()))
+                .collect::<Vec<_>>()
+                .await;
+
+            assert_eq!(txs, blocking);
+        })
+    }
+
+    #[cfg(feature = "async")]
+    #[test_case(InputFormat::Csv, CSV_RECORDS ; "Csv")]
+    #[test_case(InputFormat::Jsonl, JSONL_RECORDS ; "Jsonl")]
+    fn lines_match_records(format: InputFormat, records: &'static str) {
+        use futures::io::Cursor;
+
+        crate::rt::block_on(async {
+            let lines = format
+                .txs_with_lines_from_reader(Cursor::new(records))
+                .collect::<Vec<_>>()
+                .await;
+
+            for (line, timed) in lines {
+                let record = records.lines().nth(line as usize - 1).unwrap();
+                let client = timed.transaction.target_account_id.0;
+                assert!(record.contains(&format!("{client}")));
+            }
+        })
+    }
+
+    #[test_case("transactions.jsonl" => InputFormat::Jsonl ; "Jsonl extension")]
+    #[test_case("transactions.ndjson" => InputFormat::Jsonl ; "Ndjson extension")]
+    #[test_case("transactions.csv" => InputFormat::Csv ; "Csv extension")]
+    #[test_case("transactions" => InputFormat::Csv ; "No extension")]
+    #[test_case("-" => InputFormat::Csv ; "Stdin")]
+    #[cfg_attr(feature = "compression", test_case("transactions.jsonl.gz" => InputFormat::Jsonl ; "Compressed jsonl"))]
+    #[cfg_attr(feature = "compression", test_case("transactions.csv.zst" => InputFormat::Csv ; "Compressed csv"))]
+    fn format_from_path(path: &str) -> InputFormat {
+        InputFormat::from_path(path)
+    }
+}
@@ -1,7 +1,107 @@
+#[cfg(feature = "async")]
+mod backpressure;
+mod blocking_csv;
+mod broker;
+#[cfg(feature = "async")]
+mod broker_handle;
+#[cfg(feature = "async")]
+mod byte_record;
+#[cfg(feature = "arrow")]
+mod columnar;
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "async")]
 mod csv_broker;
+mod engine;
+mod history;
+mod jsonl;
+#[cfg(feature = "async")]
+mod merge;
+mod output_format;
+#[cfg(feature = "async")]
+mod parallel_csv;
+#[cfg(feature = "async")]
+mod rt;
+#[cfg(feature = "async")]
+mod sharded_broker;
+mod sources;
+#[cfg(feature = "async")]
+mod streaming;
+#[cfg(feature = "table")]
+mod table;
+mod tally;
+#[cfg(feature = "async")]
 mod transaction_broker;
 
-pub use crate::csv_broker::process_csv_txs;
+#[cfg(all(test, feature = "async"))]
+mod differential_tests;
+
+#[cfg(feature = "async")]
+pub use crate::backpressure::{Backpressure, QueueMetrics};
+pub use crate::blocking_csv::{
+    accounts_into_csv_writer, process_csv_txs_blocking, txs_from_csv_reader,
+};
+#[cfg(feature = "async")]
+pub use crate::broker::Broker;
+pub use crate::broker::{AccountOrder, BrokerOutput, Rejection, SequencedEvent};
+#[cfg(feature = "async")]
+pub use crate::broker_handle::{BrokerHandle, Snapshot};
+#[cfg(feature = "async")]
+pub use crate::byte_record::txs_from_reader_bytes;
+#[cfg(feature = "parquet")]
+pub use crate::columnar::{accounts_into_parquet, rejections_into_parquet};
+#[cfg(feature = "arrow")]
+pub use crate::columnar::{
+    accounts_schema, accounts_to_record_batch, rejections_schema, rejections_to_record_batch,
+    AMOUNT_PRECISION, AMOUNT_SCALE,
+};
+#[cfg(feature = "compression")]
+pub use crate::compression::Compression;
+#[cfg(feature = "async")]
+#[allow(deprecated)]
+pub use crate::csv_broker::process_csv_txs_sharded;
+#[cfg(feature = "async")]
+#[allow(deprecated)]
 pub use crate::csv_broker::process_csv_txs_sync;
+#[cfg(feature = "async")]
+#[allow(deprecated)]
+pub use crate::csv_broker::process_csv_txs_with_backpressure;
+#[cfg(feature = "async")]
+pub use crate::csv_broker::{accounts_into_csv, accounts_into_writer, deltas_into_writer};
+#[cfg(feature = "async")]
+pub use crate::csv_broker::{process_csv_txs, process_csv_txs_with_broker};
+#[cfg(feature = "async")]
+pub use crate::csv_broker::{txs_from_csv, txs_from_csv_with_parser, CsvParser};
+#[cfg(feature = "async")]
+pub use crate::csv_broker::{txs_from_reader, txs_with_lines_from_reader};
+pub use crate::engine::Engine;
+pub use crate::history::{History, HistoryEntry};
+#[cfg(feature = "async")]
+pub use crate::jsonl::{txs_from_jsonl_reader, txs_with_lines_from_jsonl_reader};
+pub use crate::jsonl::{txs_from_jsonl_reader_blocking, InputFormat};
+#[cfg(feature = "async")]
+pub use crate::merge::{txs_merged_by_timestamp, OutOfOrder, OutOfOrderRecords};
+pub use crate::output_format::OutputFormat;
+#[cfg(feature = "async")]
+pub use crate::parallel_csv::{txs_from_reader_parallel, ParallelParsing};
+#[cfg(feature = "async")]
+#[allow(deprecated)]
+pub use crate::sharded_broker::transaction_broker_sharded;
+#[cfg(feature = "async")]
+pub use crate::sharded_broker::{default_workers, ShardedBroker};
+pub use crate::sources::{expand_paths, Source, Sources};
+#[cfg(feature = "async")]
+pub use crate::sources::{txs_from_csv_files, txs_from_sources, SourceFile};
+#[cfg(feature = "async")]
+pub use crate::streaming::{Delta, Emission, StreamingBroker};
+#[cfg(feature = "table")]
+pub use crate::table::Table;
+pub use crate::tally::{Tallies, Tally, TypeTally};
+#[cfg(feature = "async")]
+#[allow(deprecated)]
 pub use crate::transaction_broker::transaction_broker;
+#[cfg(feature = "async")]
+#[allow(deprecated)]
 pub use crate::transaction_broker::transaction_broker_sync;
+#[cfg(feature = "async")]
+pub use crate::transaction_broker::{ActorBroker, SequentialBroker};
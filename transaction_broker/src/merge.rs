@@ -0,0 +1,302 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    sync::{Arc, Mutex},
+};
+
+use account::{TimedTransaction, Transaction};
+use futures::{stream, stream::BoxStream, StreamExt};
+
+use crate::sources::{Source, SourceFile, Sources};
+
+/// Record with a timestamp lower than the latest timestamp of its feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfOrder {
+    pub source: Source,
+    pub timestamp: u64,
+    /// Latest timestamp of the feed when the record was read.
+    pub latest: u64,
+}
+
+/// Records found out of order while merging, in the order they are read.
+/// Filled as the feeds are read, clones share the records.
+#[derive(Debug, Clone, Default)]
+pub struct OutOfOrderRecords(Arc<Mutex<Vec<OutOfOrder>>>);
+
+impl OutOfOrderRecords {
+    /// Records found out of order so far.
+    pub fn records(&self) -> Vec<OutOfOrder> {
+        self.0
+            .lock()
+            .expect("Out of order records must not be poisoned")
+            .clone()
+    }
+
+    fn push(&self, record: OutOfOrder) {
+        self.0
+            .lock()
+            .expect("Out of order records must not be poisoned")
+            .push(record);
+    }
+}
+
+/// Merge the transactions of the feeds by timestamp. Every feed is a list of files,
+/// read one after the other, with the stream of their transactions and line numbers.
+///
+/// Transactions with equal timestamps are taken in the order of their feeds, so the
+/// merge is stable. A transaction without a timestamp, or with a timestamp lower than
+/// the latest one of its feed, keeps its position in its feed: it is merged with the
+/// latest timestamp of its feed. Lower timestamps are recorded as out of order.
+///
+/// Returns the merged transactions, the table of their sources and the records
+/// found out of order.
+pub fn txs_merged_by_timestamp(
+    feeds: Vec<Vec<SourceFile>>,
+) -> (BoxStream<'static, Transaction>, Sources, OutOfOrderRecords) {
+    let sources = Sources::default();
+    let out_of_order = OutOfOrderRecords::default();
+
+    let feeds = feeds
+        .into_iter()
+        .map(|files| {
+            let table = sources.clone();
+            let feed: BoxStream<_> = Box::pin(stream::iter(files).flat_map(move |(path, txs)| {
+                let path = table.add_path(path);
+                txs.map(move |(line, timed)| (path, line, timed))
+            }));
+            feed
+        })
+        .collect::<Vec<_>>();
+
+    let merge = Merge {
+        latest: vec![0; feeds.len()],
+        heads: (0..feeds.len()).map(|_| None).collect(),
+        order: BinaryHeap::with_capacity(feeds.len()),
+        feeds,
+        started: false,
+        sources: sources.clone(),
+        out_of_order: out_of_order.clone(),
+    };
+    let txs = stream::unfold(merge, |mut merge| async move {
+        let transaction = merge.next_transaction().await?;
+        Some((transaction, merge))
+    });
+
+    (Box::pin(txs), sources, out_of_order)
+}
+
+/// K-way merge of the feeds, holding the next transaction of every feed.
+struct Merge {
+    /// Transactions of every feed, with the index of their file and their line.
+    feeds: Vec<BoxStream<'static, (usize, u64, TimedTransaction)>>,
+    /// Next transaction of every feed, with the index of its file and its line.
+    heads: Vec<Option<(usize, u64, Transaction)>>,
+    /// Feeds with a next transaction, by lowest timestamp, then lowest feed.
+    order: BinaryHeap<Reverse<(u64, usize)>>,
+    /// Latest timestamp of every feed.
+    latest: Vec<u64>,
+    /// `false` until the first transaction of every feed is read.
+    started: bool,
+    sources: Sources,
+    out_of_order: OutOfOrderRecords,
+}
+
+impl Merge {
+    /// Next transaction of the merge. Returns `None` once every feed is exhausted.
+    async fn next_transaction(&mut self) -> Option<Transaction> {
+        if !self.started {
+            for feed in 0..self.feeds.len() {
+                self.read_head(feed).await;
+            }
+            self.started = true;
+        }
+
+        let Reverse((_timestamp, feed)) = self.order.pop()?;
+        let (path, line, transaction) = self.heads[feed]
+            .take()
+            .expect("Feeds in the merge order must have a next transaction");
+        self.sources.push(path, line);
+        self.read_head(feed).await;
+
+        Some(transaction)
+    }
+
+    /// Read the next transaction of the feed, if there is one.
+    async fn read_head(&mut self, feed: usize) {
+        let Some((path, line, timed)) = self.feeds[feed].next().await else {
+            return;
+        };
+
+        let latest = self.latest[feed];
+        match timed.timestamp {
+            Some(timestamp) if timestamp < latest => {
+                let out_of_order = OutOfOrder {
+                    source: Source {
+                        path: self.sources.path(path),
+                        line,
+                    },
+                    timestamp,
+                    latest,
+                };
+                #[cfg(feature = "tracing")]
+                tracing::warn!(?out_of_order, "Record out of order");
+                self.out_of_order.push(out_of_order);
+            }
+            Some(timestamp) => self.latest[feed] = timestamp,
+            None => {}
+        }
+
+        self.heads[feed] = Some((path, line, timed.transaction));
+        self.order.push(Reverse((self.latest[feed], feed)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use account::TransactionKind;
+    use futures::io::Cursor;
+
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::txs_with_lines_from_reader;
+
+    fn feed(path: &str, input: &'static str) -> SourceFile {
+        (
+            PathBuf::from(path),
+            Box::pin(txs_with_lines_from_reader(Cursor::new(input))),
+        )
+    }
+
+    fn merged_tx_ids(feeds: Vec<Vec<SourceFile>>) -> (Vec<u32>, Sources, OutOfOrderRecords) {
+        crate::rt::block_on(async {
+            let (txs, sources, out_of_order) = txs_merged_by_timestamp(feeds);
+            let tx_ids = txs
+                .map(|transaction| match transaction.kind {
+                    TransactionKind::Deposit(deposit) => deposit.tx_id().0,
+                    _ => unreachable!("Feeds only hold deposits"),
+                })
+                .collect()
+                .await;
+
+            (tx_ids, sources, out_of_order)
+        })
+    }
+
+    #[test]
+    fn feeds_are_interleaved_by_timestamp() {
+        let partner_a = "type,client,tx,amount,timestamp
+deposit,1,1,1.0,10
+deposit,1,2,1.0,30
+deposit,1,3,1.0,50
+";
+        let partner_b = "type,client,tx,amount,timestamp
+deposit,2,4,1.0,20
+deposit,2,5,1.0,30
+deposit,2,6,1.0,60
+";
+
+        let (tx_ids, sources, out_of_order) = merged_tx_ids(vec![
+            vec![feed("a.csv", partner_a)],
+            vec![feed("b.csv", partner_b)],
+        ]);
+
+        // equal timestamps are taken in the order of the feeds
+        assert_eq!(tx_ids, [1, 4, 2, 5, 3, 6]);
+        assert_eq!(
+            sources.source(3),
+            Some(Source {
+                path: PathBuf::from("b.csv"),
+                line: 3
+            })
+        );
+        assert!(out_of_order.records().is_empty());
+    }
+
+    #[test]
+    fn files_of_a_feed_are_read_in_order() {
+        let day_1 = "type,client,tx,amount,timestamp\ndeposit,1,1,1.0,10\n";
+        let day_2 = "type,client,tx,amount,timestamp\ndeposit,1,2,1.0,40\n";
+        let partner_b = "type,client,tx,amount,timestamp\ndeposit,2,3,1.0,20\n";
+
+        let (tx_ids, sources, _) = merged_tx_ids(vec![
+            vec![feed("day1.csv", day_1), feed("day2.csv", day_2)],
+            vec![feed("b.csv", partner_b)],
+        ]);
+
+        assert_eq!(tx_ids, [1, 3, 2]);
+        assert_eq!(
+            sources.source(2).map(|source| source.path),
+            Some(PathBuf::from("day2.csv"))
+        );
+    }
+
+    #[test]
+    fn out_of_order_records_keep_their_position() {
+        let partner_a = "type,client,tx,amount,timestamp
+deposit,1,1,1.0,10
+deposit,1,2,1.0,40
+deposit,1,3,1.0,20
+deposit,1,4,1.0,
+deposit,1,5,1.0,50
+";
+        let partner_b = "type,client,tx,amount,timestamp
+deposit,2,6,1.0,30
+deposit,2,7,1.0,45
+";
+
+        let (tx_ids, _, out_of_order) = merged_tx_ids(vec![
+            vec![feed("a.csv", partner_a)],
+            vec![feed("b.csv", partner_b)],
+        ]);
+
+        assert_eq!(tx_ids, [1, 6, 2, 3, 4, 7, 5]);
+        assert_eq!(
+            out_of_order.records(),
+            [OutOfOrder {
+                source: Source {
+                    path: PathBuf::from("a.csv"),
+                    line: 4
+                },
+                timestamp: 20,
+                latest: 40,
+            }]
+        );
+    }
+
+    #[test]
+    fn records_without_timestamps_are_concatenated() {
+        let first = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,1.0\n";
+        let second = "type,client,tx,amount\ndeposit,2,3,1.0\n";
+
+        let (tx_ids, _, _) = merged_tx_ids(vec![
+            vec![feed("first.csv", first)],
+            vec![feed("second.csv", second)],
+        ]);
+
+        assert_eq!(tx_ids, [1, 2, 3]);
+    }
+
+    #[test]
+    fn timestamps_are_read_from_json_lines() {
+        use crate::InputFormat;
+
+        let partner_a =
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0", "timestamp": 20}"#;
+        let partner_b =
+            r#"{"type": "deposit", "client": 2, "tx": 2, "amount": "1.0", "timestamp": 10}"#;
+        let jsonl_feed = |path: &str, input: &'static str| {
+            (
+                PathBuf::from(path),
+                InputFormat::Jsonl.txs_with_lines_from_reader(Cursor::new(input)),
+            )
+        };
+
+        let (tx_ids, _, _) = merged_tx_ids(vec![
+            vec![jsonl_feed("a.jsonl", partner_a)],
+            vec![jsonl_feed("b.jsonl", partner_b)],
+        ]);
+
+        assert_eq!(tx_ids, [2, 1]);
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use account::{AccountId, AccountState, IsLocked};
+#[cfg(feature = "async")]
+use futures::{future, AsyncWrite, AsyncWriteExt, Stream, StreamExt};
+use serde::Serialize;
+
+use crate::blocking_csv::accounts_into_csv_writer;
+#[cfg(feature = "parquet")]
+use crate::columnar::accounts_into_parquet;
+#[cfg(feature = "async")]
+use crate::csv_broker::accounts_into_writer;
+#[cfg(feature = "table")]
+use crate::table::Table;
+
+/// Format of the account states output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Csv records with a header line.
+    #[default]
+    Csv,
+    /// Json array of account objects.
+    Json,
+    /// Newline delimited json account objects.
+    Jsonl,
+    /// Human readable table with aligned columns.
+    #[cfg(feature = "table")]
+    Table(Table),
+    /// Parquet file, with decimal amount columns.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl OutputFormat {
+    /// Write the account states into the writer, in the order they are received.
+    /// Account states that can't be represented as a record are ignored.
+    ///
+    /// # Errors
+    /// Writing a record fails.
+    pub fn accounts_into_writer_blocking(
+        self,
+        mut writer: impl Write,
+        account_states: impl IntoIterator<Item = AccountState>,
+    ) -> io::Result<()> {
+        match self {
+            OutputFormat::Csv => return Ok(accounts_into_csv_writer(writer, account_states)?),
+            #[cfg(feature = "table")]
+            OutputFormat::Table(table) => {
+                return table.accounts_into_writer(writer, account_states)
+            }
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => {
+                let states = account_states.into_iter().collect::<Vec<_>>();
+                let mut output = Vec::new();
+                accounts_into_parquet(&mut output, &states).map_err(io::Error::other)?;
+                writer.write_all(&output)?;
+                return writer.flush();
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {}
+        }
+
+        let mut position = 0;
+        for record in account_states.into_iter().filter_map(json_record) {
+            writer.write_all(&self.json_line(position, &record)?)?;
+            position += 1;
+        }
+
+        writer.write_all(self.json_end(position))?;
+        writer.flush()
+    }
+
+    /// Write the account states into the writer, in the order they are received.
+    /// Account states that can't be represented as a record are ignored.
+    ///
+    /// # Errors
+    /// Writing a json record fails. Csv write errors are ignored.
+    #[cfg(feature = "async")]
+    pub async fn accounts_into_writer(
+        self,
+        mut writer: impl AsyncWrite + Unpin,
+        account_states: impl Stream<Item = AccountState> + Unpin,
+    ) -> io::Result<()> {
+        match self {
+            OutputFormat::Csv => {
+                accounts_into_writer(writer, account_states).await;
+                return Ok(());
+            }
+            #[cfg(feature = "table")]
+            OutputFormat::Table(_) => {
+                return self
+                    .accounts_into_writer_at_end(writer, account_states)
+                    .await
+            }
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => {
+                return self
+                    .accounts_into_writer_at_end(writer, account_states)
+                    .await
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {}
+        }
+
+        let mut records = account_states.filter_map(|state| future::ready(json_record(state)));
+        let mut position = 0;
+        while let Some(record) = records.next().await {
+            writer
+                .write_all(&self.json_line(position, &record)?)
+                .await?;
+            position += 1;
+        }
+
+        writer.write_all(self.json_end(position)).await?;
+        writer.flush().await
+    }
+
+    /// Write the account states once all of them are received. Tables are aligned and
+    /// parquet files are written with all the rows known.
+    #[cfg(all(feature = "async", any(feature = "table", feature = "parquet")))]
+    async fn accounts_into_writer_at_end(
+        self,
+        mut writer: impl AsyncWrite + Unpin,
+        account_states: impl Stream<Item = AccountState> + Unpin,
+    ) -> io::Result<()> {
+        let mut output = Vec::new();
+        self.accounts_into_writer_blocking(&mut output, account_states.collect::<Vec<_>>().await)?;
+        writer.write_all(&output).await?;
+        writer.flush().await
+    }
+
+    /// Json record, preceded by the separator of its position in the output.
+    fn json_line(self, position: usize, record: &AccountJsonRecord) -> io::Result<Vec<u8>> {
+        let mut line = match (self, position) {
+            (OutputFormat::Json, 0) => b"[\n".to_vec(),
+            (OutputFormat::Json, _) => b",\n".to_vec(),
+            _ => Vec::new(),
+        };
+        serde_json::to_writer(&mut line, record)?;
+        if self == OutputFormat::Jsonl {
+            line.push(b'\n');
+        }
+
+        Ok(line)
+    }
+
+    /// End of the json output with the given number of records.
+    fn json_end(self, records: usize) -> &'static [u8] {
+        match (self, records) {
+            (OutputFormat::Json, 0) => b"[]\n",
+            (OutputFormat::Json, _) => b"\n]\n",
+            _ => b"",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            #[cfg(feature = "table")]
+            "table" => Ok(OutputFormat::Table(Table::default())),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(format!(
+                "Unknown output format {format}, expected {}",
+                expected_formats()
+            )),
+        }
+    }
+}
+
+/// Names of the output formats enabled by the features, as "csv, json or jsonl".
+fn expected_formats() -> String {
+    let names = [
+        "csv",
+        "json",
+        "jsonl",
+        #[cfg(feature = "table")]
+        "table",
+        #[cfg(feature = "parquet")]
+        "parquet",
+    ];
+    let (last, rest) = names.split_last().expect("Csv is always enabled");
+
+    format!("{} or {last}", rest.join(", "))
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Csv => write!(f, "csv"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Jsonl => write!(f, "jsonl"),
+            #[cfg(feature = "table")]
+            OutputFormat::Table(_) => write!(f, "table"),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => write!(f, "parquet"),
+        }
+    }
+}
+
+/// Json representation of an account state. Amounts are strings with exactly
+/// [`account::Amount::DECIMAL_POINTS`] decimals, so they are not read as floats.
+#[derive(Debug, Serialize)]
+struct AccountJsonRecord {
+    client: AccountId,
+    available: String,
+    held: String,
+    total: String,
+    locked: bool,
+}
+
+impl TryFrom<AccountState> for AccountJsonRecord {
+    type Error = account::Error;
+
+    fn try_from(state: AccountState) -> Result<Self, Self::Error> {
+        Ok(AccountJsonRecord {
+            total: state.total()?.to_fixed_string(),
+            client: state.id,
+            available: state.available.to_fixed_string(),
+            held: state.held.to_fixed_string(),
+            locked: state.is_locked == IsLocked::Locked,
+        })
+    }
+}
+
+fn json_record(state: AccountState) -> Option<AccountJsonRecord> {
+    let record = AccountJsonRecord::try_from(state);
+
+    #[cfg(feature = "tracing")]
+    if record.is_err() {
+        tracing::error!(err = ?record, "Failed to serialize record");
+    }
+
+    record.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use account::Amount;
+    use test_case::test_case;
+
+    use super::*;
+
+    fn states() -> Vec<AccountState> {
+        vec![
+            AccountState {
+                id: AccountId(2),
+                available: Amount::from_fixed_point(105, 1).unwrap(),
+                held: Amount::MIN,
+                is_locked: IsLocked::Unlocked,
+            },
+            AccountState {
+                id: AccountId(300),
+                available: Amount::MIN,
+                held: Amount::from_u64(3),
+                is_locked: IsLocked::Locked,
+            },
+        ]
+    }
+
+    fn output_blocking(format: OutputFormat, states: Vec<AccountState>) -> String {
+        String::from_utf8(output_bytes_blocking(format, states)).unwrap()
+    }
+
+    fn output_bytes_blocking(format: OutputFormat, states: Vec<AccountState>) -> Vec<u8> {
+        let mut output = Vec::new();
+        format
+            .accounts_into_writer_blocking(&mut output, states)
+            .unwrap();
+
+        output
+    }
+
+    #[test_case(OutputFormat::Json, states() => r#"[
+{"client":2,"available":"10.5000","held":"0.0000","total":"10.5000","locked":false},
+{"client":300,"available":"0.0000","held":"3.0000","total":"3.0000","locked":true}
+]
+"# ; "Json")]
+    #[test_case(OutputFormat::Jsonl, states() => r#"{"client":2,"available":"10.5000","held":"0.0000","total":"10.5000","locked":false}
+{"client":300,"available":"0.0000","held":"3.0000","total":"3.0000","locked":true}
+"# ; "Jsonl")]
+    #[test_case(OutputFormat::Json, vec![] => "[]\n" ; "Empty json")]
+    #[test_case(OutputFormat::Jsonl, vec![] => "" ; "Empty jsonl")]
+    fn json_output(format: OutputFormat, states: Vec<AccountState>) -> String {
+        output_blocking(format, states)
+    }
+
+    #[test_case("json" => Ok(OutputFormat::Json) ; "Json")]
+    #[cfg_attr(not(any(feature = "table", feature = "parquet")), test_case("xml" => Err("Unknown output format xml, expected csv, json or jsonl".to_string()) ; "Unknown format"))]
+    #[cfg_attr(all(feature = "table", feature = "parquet"), test_case("xml" => Err("Unknown output format xml, expected csv, json, jsonl, table or parquet".to_string()) ; "Unknown format"))]
+    fn output_format_from_str(format: &str) -> Result<OutputFormat, String> {
+        format.parse()
+    }
+
+    #[test]
+    fn json_array_is_valid_json() {
+        let output = output_blocking(OutputFormat::Json, states());
+        let accounts: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[1]["held"], "3.0000");
+    }
+
+    #[cfg(feature = "async")]
+    #[test_case(OutputFormat::Csv ; "Csv")]
+    #[test_case(OutputFormat::Json ; "Json")]
+    #[test_case(OutputFormat::Jsonl ; "Jsonl")]
+    #[cfg_attr(feature = "table", test_case(OutputFormat::Table(Table::default()) ; "Table"))]
+    #[cfg_attr(feature = "parquet", test_case(OutputFormat::Parquet ; "Parquet"))]
+    fn async_output_matches_blocking_output(format: OutputFormat) {
+        crate::rt::block_on(async {
+            let mut output = Vec::new();
+            format
+                .accounts_into_writer(&mut output, futures::stream::iter(states()))
+                .await
+                .unwrap();
+
+            assert_eq!(output, output_bytes_blocking(format, states()));
+        })
+    }
+}
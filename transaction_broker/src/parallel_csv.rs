@@ -0,0 +1,190 @@
+use std::{num::NonZeroUsize, thread};
+
+use account::Transaction;
+use futures::{stream, AsyncRead, AsyncReadExt, Stream, StreamExt};
+
+use crate::rt;
+
+/// Configuration of the parallel csv parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParallelParsing {
+    /// Minimum number of bytes in a chunk. Chunks are extended to the next record boundary.
+    pub chunk_size: NonZeroUsize,
+    /// Maximum number of chunks parsed at the same time.
+    pub parsers: NonZeroUsize,
+}
+
+impl Default for ParallelParsing {
+    /// 256 KiB chunks, one parser per available core.
+    fn default() -> Self {
+        ParallelParsing {
+            chunk_size: NonZeroUsize::new(256 * 1024).unwrap(),
+            parsers: thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+        }
+    }
+}
+
+/// Split the reader into chunks of whole records and deserialize the chunks in parallel.
+/// Transactions are emitted in the input order.
+/// Fields are assigned based on headers.
+/// If a record can't be deserialized it is ignored.
+///
+/// Records are split on line breaks, so quoted fields must not contain line breaks.
+pub fn txs_from_reader_parallel(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+    config: &ParallelParsing,
+) -> impl Stream<Item = Transaction> {
+    let chunker = Chunker {
+        reader,
+        chunk_size: config.chunk_size.get(),
+        header: None,
+        remainder: Vec::new(),
+    };
+
+    futures::stream::unfold(chunker, |mut chunker| async move {
+        let chunk = chunker.next_chunk().await?;
+        Some((chunk, chunker))
+    })
+    .map(|chunk| rt::spawn_blocking(move || parse_chunk(&chunk)))
+    // buffered keeps the chunk order, while up to `parsers` chunks are parsed
+    .buffered(config.parsers.get())
+    .flat_map(stream::iter)
+}
+
+/// Splits the input on line breaks. Every chunk starts with the header line,
+/// so it can be deserialized on its own.
+struct Chunker<R> {
+    reader: R,
+    chunk_size: usize,
+    /// Header line, including the line break. `None` until the first line is read.
+    header: Option<Vec<u8>>,
+    /// Bytes read after the last line break of the previous chunk.
+    remainder: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> Chunker<R> {
+    /// Next chunk of whole records, prefixed by the header line.
+    /// Returns `None` once the input is exhausted.
+    ///
+    /// # Errors
+    /// Read errors end the input.
+    async fn next_chunk(&mut self) -> Option<Vec<u8>> {
+        let mut buffer = std::mem::take(&mut self.remainder);
+
+        let end = loop {
+            let last_line_break = buffer.iter().rposition(|byte| *byte == b'\n');
+
+            match (&self.header, last_line_break) {
+                (None, Some(_)) => {
+                    let header_end = buffer.iter().position(|byte| *byte == b'\n').unwrap() + 1;
+                    self.header = Some(buffer.drain(..header_end).collect());
+                    continue;
+                }
+                (Some(_), Some(last_line_break)) if buffer.len() >= self.chunk_size => {
+                    break last_line_break + 1;
+                }
+                _ => {}
+            }
+
+            if !self.fill(&mut buffer).await {
+                break buffer.len();
+            }
+        };
+
+        self.remainder = buffer.split_off(end);
+
+        if buffer.is_empty() {
+            return None;
+        }
+
+        let header = self.header.as_deref().unwrap_or_default();
+        let mut chunk = Vec::with_capacity(header.len() + buffer.len());
+        chunk.extend_from_slice(header);
+        chunk.extend_from_slice(&buffer);
+
+        Some(chunk)
+    }
+
+    /// Read up to `chunk_size` bytes into the buffer. Returns false at the end of the input.
+    async fn fill(&mut self, buffer: &mut Vec<u8>) -> bool {
+        let filled = buffer.len();
+        buffer.resize(filled + self.chunk_size, 0);
+
+        let read = self.reader.read(&mut buffer[filled..]).await;
+        buffer.truncate(filled + read.as_ref().map_or(0, |read| *read));
+
+        #[cfg(feature = "tracing")]
+        if read.is_err() {
+            tracing::error!(err = ?read, "Failed to read the input");
+        }
+
+        matches!(read, Ok(read) if read > 0)
+    }
+}
+
+/// Deserialize a chunk of csv records, starting with the header line.
+fn parse_chunk(chunk: &[u8]) -> Vec<Transaction> {
+    csv::Reader::from_reader(chunk)
+        .into_deserialize()
+        .filter_map(|result| {
+            #[cfg(feature = "tracing")]
+            if result.is_err() {
+                tracing::error!(err = ?result, "Failed to deserialize record");
+            }
+
+            result.ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::io::Cursor;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::rt;
+    use crate::txs_from_reader;
+
+    const RECORDS: &str = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.5
+withdraw,1,3,0.5
+not a record
+dispute,2,2,
+resolve,2,2,
+deposit,3,4,10.1234
+chargeback,2,2,";
+
+    async fn parse(input: String, chunk_size: usize, parsers: usize) -> Vec<Transaction> {
+        let config = ParallelParsing {
+            chunk_size: NonZeroUsize::new(chunk_size).unwrap(),
+            parsers: NonZeroUsize::new(parsers).unwrap(),
+        };
+
+        txs_from_reader_parallel(Cursor::new(input), &config)
+            .collect()
+            .await
+    }
+
+    async fn parse_sequential(input: String) -> Vec<Transaction> {
+        txs_from_reader(Cursor::new(input)).collect().await
+    }
+
+    #[test_case(RECORDS.to_owned() ; "No trailing line break")]
+    #[test_case(format!("{RECORDS}\n") ; "Trailing line break")]
+    #[test_case(RECORDS.replace('\n', "\r\n") ; "CRLF line breaks")]
+    #[test_case("type,client,tx,amount\n".to_owned() ; "Only header")]
+    #[test_case(String::new() ; "Empty input")]
+    fn parallel_parsing_matches_sequential(input: String) {
+        rt::block_on(async {
+            let expected = parse_sequential(input.clone()).await;
+
+            for chunk_size in [1, 7, 16, 64, 4096] {
+                for parsers in [1, 3] {
+                    assert_eq!(parse(input.clone(), chunk_size, parsers).await, expected);
+                }
+            }
+        })
+    }
+}
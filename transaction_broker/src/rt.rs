@@ -0,0 +1,140 @@
+//! Runtime abstraction. Tasks and files are provided by the runtime selected with
+//! the `rt-async-std` or `rt-tokio` feature. Tokio is used if both are enabled.
+
+#[cfg(not(any(feature = "rt-async-std", feature = "rt-tokio")))]
+compile_error!("The async feature requires a runtime. Enable `rt-async-std` or `rt-tokio`.");
+
+#[cfg(all(feature = "rt-async-std", not(feature = "rt-tokio")))]
+pub(crate) use self::async_std_rt::*;
+#[cfg(feature = "rt-tokio")]
+pub(crate) use self::tokio_rt::*;
+
+#[cfg(all(feature = "rt-async-std", not(feature = "rt-tokio")))]
+mod async_std_rt {
+    use std::{future::Future, io, path::Path, time::Duration};
+
+    use async_std::{fs::File, task};
+    use futures::{AsyncRead, AsyncWrite, Stream};
+
+    pub(crate) use async_std::task::JoinHandle;
+
+    pub(crate) fn spawn<F>(future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        task::spawn(future)
+    }
+
+    pub(crate) fn spawn_blocking<F, T>(f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        task::spawn_blocking(f)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        task::block_on(future)
+    }
+
+    /// Yields after every period.
+    pub(crate) fn interval(period: Duration) -> impl Stream<Item = ()> + Send {
+        async_std::stream::interval(period)
+    }
+
+    pub(crate) async fn open(
+        path: impl AsRef<Path>,
+    ) -> io::Result<impl AsyncRead + Unpin + Send + 'static> {
+        File::open(path.as_ref()).await
+    }
+
+    pub(crate) async fn create(
+        path: impl AsRef<Path>,
+    ) -> io::Result<impl AsyncWrite + Unpin + Send + 'static> {
+        File::create(path.as_ref()).await
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+mod tokio_rt {
+    use std::{
+        future::Future,
+        io, panic,
+        path::Path,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use futures::{AsyncRead, AsyncWrite, Stream};
+    use tokio::{fs::File, task, time};
+    use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+    /// Resolves to the output of the task. Panics of the task are propagated.
+    #[derive(Debug)]
+    pub(crate) struct JoinHandle<T>(task::JoinHandle<T>);
+
+    impl<T> Future for JoinHandle<T> {
+        type Output = T;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+            Pin::new(&mut self.0).poll(cx).map(|result| match result {
+                Ok(output) => output,
+                Err(err) => panic::resume_unwind(err.into_panic()),
+            })
+        }
+    }
+
+    pub(crate) fn spawn<F>(future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        JoinHandle(task::spawn(future))
+    }
+
+    pub(crate) fn spawn_blocking<F, T>(f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        JoinHandle(task::spawn_blocking(f))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("Must be able to start the runtime")
+            .block_on(future)
+    }
+
+    /// Yields after every period. The timer starts when the stream is first polled,
+    /// so the stream can be created outside of the runtime.
+    pub(crate) fn interval(period: Duration) -> impl Stream<Item = ()> + Send {
+        futures::stream::unfold(None, move |interval| async move {
+            let mut interval = interval.unwrap_or_else(|| {
+                let mut interval = time::interval_at(time::Instant::now() + period, period);
+                interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+                interval
+            });
+            interval.tick().await;
+            Some(((), Some(interval)))
+        })
+    }
+
+    pub(crate) async fn open(
+        path: impl AsRef<Path>,
+    ) -> io::Result<impl AsyncRead + Unpin + Send + 'static> {
+        Ok(File::open(path).await?.compat())
+    }
+
+    pub(crate) async fn create(
+        path: impl AsRef<Path>,
+    ) -> io::Result<impl AsyncWrite + Unpin + Send + 'static> {
+        Ok(File::create(path).await?.compat_write())
+    }
+}
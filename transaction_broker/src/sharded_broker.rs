@@ -0,0 +1,224 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    future::Future,
+    hash::{Hash, Hasher},
+    num::NonZeroUsize,
+    thread::{self, JoinHandle},
+};
+
+use async_channel::{Receiver, Sender};
+
+use account::{Account, AccountId, Transaction};
+use futures::{Stream, StreamExt};
+
+use crate::broker::{try_apply, AccountOrder, Broker, BrokerOutput, Rejection, SequencedEvent};
+use crate::rt;
+use crate::tally::Tallies;
+
+/// This error should never happen. This must be satisfied by inspection.
+const CLOSED_CHANNEL_ERROR: &str = "Workers must have open channels until the input ends";
+
+/// Number of workers used by the sharded broker when none is configured.
+/// Equals the available parallelism.
+pub fn default_workers() -> NonZeroUsize {
+    thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
+/// Handles the transactions on a fixed pool of worker threads. Accounts are partitioned
+/// between the workers by the hash of their id.
+#[derive(Debug, Clone)]
+pub struct ShardedBroker {
+    pub order: AccountOrder,
+    pub workers: NonZeroUsize,
+    /// Maximum number of transactions queued for every worker. Once a worker queue is
+    /// full the broker stops reading the input stream until the worker catches up.
+    /// The queues are unbounded when `None`.
+    pub channel_capacity: Option<usize>,
+}
+
+impl Default for ShardedBroker {
+    /// One worker per available core.
+    fn default() -> Self {
+        ShardedBroker {
+            order: AccountOrder::default(),
+            workers: default_workers(),
+            channel_capacity: None,
+        }
+    }
+}
+
+impl Broker for ShardedBroker {
+    fn process(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> impl Future<Output = BrokerOutput> {
+        transaction_broker_sharded_with_events(transactions, self, None)
+    }
+
+    fn process_with_events(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> (
+        impl Stream<Item = SequencedEvent>,
+        impl Future<Output = BrokerOutput>,
+    ) {
+        let (events, receiver) = async_channel::unbounded();
+        let output = transaction_broker_sharded_with_events(transactions, self, Some(events));
+
+        (receiver, output)
+    }
+}
+
+/// Partition of the accounts owned by a single worker.
+#[derive(Default)]
+struct Shard {
+    accounts: HashMap<AccountId, Account>,
+    /// Sequence number of the first transaction of every account.
+    first_seen: Vec<(u64, AccountId)>,
+    rejections: Vec<Rejection>,
+    tallies: Tallies,
+}
+
+impl Shard {
+    fn run(
+        receiver: Receiver<(u64, Transaction)>,
+        events: Option<async_channel::Sender<SequencedEvent>>,
+    ) -> Self {
+        let mut shard = Shard::default();
+
+        while let Ok((sequence, tx_request)) = receiver.recv_blocking() {
+            let account = shard
+                .accounts
+                .entry(tx_request.target_account_id.clone())
+                .or_insert_with_key(|account_id| {
+                    shard.first_seen.push((sequence, account_id.clone()));
+                    Account::from_id(account_id.clone())
+                });
+
+            shard.rejections.extend(try_apply(
+                account,
+                sequence,
+                tx_request,
+                events.as_ref(),
+                &mut shard.tallies,
+            ));
+        }
+
+        shard
+    }
+}
+
+/// Handle the transactions on a fixed pool of worker threads.
+#[deprecated(note = "Use `ShardedBroker` through the `Broker` trait")]
+pub async fn transaction_broker_sharded(
+    transaction_requests: impl Stream<Item = Transaction> + Unpin,
+    order: AccountOrder,
+    workers: NonZeroUsize,
+) -> BrokerOutput {
+    ShardedBroker {
+        order,
+        workers,
+        ..ShardedBroker::default()
+    }
+    .process(transaction_requests)
+    .await
+}
+
+/// Worker owning the account.
+fn shard_of(account_id: &AccountId, workers: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    account_id.hash(&mut hasher);
+    (hasher.finish() % workers as u64) as usize
+}
+
+/// Handle the transactions on a fixed pool of worker threads. Accounts are partitioned
+/// between the workers by the hash of their id. Every worker applies the transactions of
+/// its accounts in the order they are received, preserving the per account transaction
+/// order.
+///
+/// Worker channels are bounded by the broker `channel_capacity`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(transaction_requests, events))
+)]
+async fn transaction_broker_sharded_with_events(
+    mut transaction_requests: impl Stream<Item = Transaction> + Unpin,
+    broker: &ShardedBroker,
+    events: Option<async_channel::Sender<SequencedEvent>>,
+) -> BrokerOutput {
+    let (senders, handlers): (Vec<Sender<_>>, Vec<JoinHandle<Shard>>) = (0..broker.workers.get())
+        .map(|_| {
+            let (sender, receiver) = match broker.channel_capacity {
+                Some(capacity) => async_channel::bounded(capacity),
+                None => async_channel::unbounded(),
+            };
+            let events = events.clone();
+            (sender, thread::spawn(move || Shard::run(receiver, events)))
+        })
+        .unzip();
+
+    let mut sequence = 0;
+    while let Some(tx_request) = transaction_requests.next().await {
+        let worker = shard_of(&tx_request.target_account_id, senders.len());
+        let _res = senders[worker].send((sequence, tx_request)).await;
+
+        #[cfg(feature = "tracing")]
+        if _res.is_err() {
+            tracing::error!(error = %CLOSED_CHANNEL_ERROR);
+        }
+
+        debug_assert!(_res.is_ok(), "{}", CLOSED_CHANNEL_ERROR);
+        sequence += 1;
+    }
+
+    // closing the channels stops the workers
+    drop(senders);
+    let shards = rt::spawn_blocking(move || {
+        handlers
+            .into_iter()
+            .map(|handler| handler.join().expect("Workers must not panic"))
+            .collect::<Vec<_>>()
+    })
+    .await;
+
+    merge_shards(shards, broker.order)
+}
+
+fn merge_shards(shards: Vec<Shard>, order: AccountOrder) -> BrokerOutput {
+    let mut accounts = HashMap::new();
+    let mut first_seen = Vec::new();
+    let mut rejections = Vec::new();
+    let mut tallies = Tallies::default();
+
+    for shard in shards {
+        accounts.extend(shard.accounts);
+        first_seen.extend(shard.first_seen);
+        rejections.extend(shard.rejections);
+        tallies.merge(shard.tallies);
+    }
+
+    first_seen.sort_unstable_by_key(|(sequence, _)| *sequence);
+    rejections.sort_unstable_by_key(|rejection| rejection.sequence);
+
+    let first_seen = first_seen
+        .into_iter()
+        .map(|(_, account_id)| account_id)
+        .collect();
+
+    let accounts = order
+        .arrange(first_seen)
+        .into_iter()
+        .map(|account_id| {
+            accounts
+                .remove(&account_id)
+                .expect("Every seen account must be present")
+                .into_state()
+        })
+        .collect();
+
+    BrokerOutput {
+        accounts,
+        rejections,
+        tallies,
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "async")]
+use account::{TimedTransaction, Transaction};
+#[cfg(feature = "async")]
+use futures::{stream, stream::BoxStream, StreamExt};
+
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+#[cfg(feature = "async")]
+use crate::csv_broker::txs_with_lines_from_reader;
+#[cfg(feature = "async")]
+use crate::rt;
+
+/// Input file and line of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub path: PathBuf,
+    /// Line number of the record, starting at 1.
+    pub line: u64,
+}
+
+/// Input file, with the stream of its transactions, their line numbers and timestamps.
+#[cfg(feature = "async")]
+pub type SourceFile = (PathBuf, BoxStream<'static, (u64, TimedTransaction)>);
+
+/// Side table of the input files and lines of the transactions, by their position
+/// in the concatenated input. Filled as the transactions are read, clones share the table.
+///
+/// The position of a transaction is the sequence number given by the brokers, so the
+/// table finds the source of a [`Rejection`](crate::Rejection).
+#[derive(Debug, Clone, Default)]
+pub struct Sources(Arc<Mutex<SourceTable>>);
+
+#[derive(Debug, Default)]
+struct SourceTable {
+    paths: Vec<PathBuf>,
+    /// Runs of transactions read from consecutive lines, ordered by their first position.
+    runs: Vec<Run>,
+    /// Number of transactions read.
+    len: u64,
+}
+
+/// Transactions read from consecutive lines of a file.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    sequence: u64,
+    path: usize,
+    line: u64,
+}
+
+impl Sources {
+    /// Source of the transaction at the given position of the input.
+    /// `None` if the transaction was not read yet.
+    pub fn source(&self, sequence: u64) -> Option<Source> {
+        let table = self.0.lock().expect("Source table must not be poisoned");
+        if sequence >= table.len {
+            return None;
+        }
+
+        let run = table.runs[table.runs.partition_point(|run| run.sequence <= sequence) - 1];
+        Some(Source {
+            path: table.paths[run.path].clone(),
+            line: run.line + (sequence - run.sequence),
+        })
+    }
+
+    /// Add a file to the table, returning its index.
+    #[cfg(feature = "async")]
+    pub(crate) fn add_path(&self, path: PathBuf) -> usize {
+        let mut table = self.0.lock().expect("Source table must not be poisoned");
+        table.paths.push(path);
+        table.paths.len() - 1
+    }
+
+    /// Path of the file at the given index.
+    #[cfg(feature = "async")]
+    pub(crate) fn path(&self, path: usize) -> PathBuf {
+        let table = self.0.lock().expect("Source table must not be poisoned");
+        table.paths[path].clone()
+    }
+
+    /// Record the source of the next transaction of the input.
+    #[cfg(feature = "async")]
+    pub(crate) fn push(&self, path: usize, line: u64) {
+        let mut table = self.0.lock().expect("Source table must not be poisoned");
+        let sequence = table.len;
+        let extends_last_run = table
+            .runs
+            .last()
+            .is_some_and(|run| run.path == path && run.line + (sequence - run.sequence) == line);
+
+        if !extends_last_run {
+            table.runs.push(Run {
+                sequence,
+                path,
+                line,
+            });
+        }
+        table.len += 1;
+    }
+}
+
+/// Concatenate the transactions of the inputs, in the given order. Every input is
+/// a file with the stream of its transactions and their line numbers. Timestamps are ignored.
+///
+/// Returns the concatenated transactions, and the table of their sources.
+#[cfg(feature = "async")]
+pub fn txs_from_sources(inputs: Vec<SourceFile>) -> (BoxStream<'static, Transaction>, Sources) {
+    let sources = Sources::default();
+    let table = sources.clone();
+
+    let txs = stream::iter(inputs).flat_map(move |(path, txs)| {
+        let table = table.clone();
+        let path = table.add_path(path);
+
+        txs.map(move |(line, timed)| {
+            table.push(path, line);
+            timed.transaction
+        })
+    });
+
+    (Box::pin(txs), sources)
+}
+
+/// Read the csv files and deserialize them using serde, concatenated in the given order.
+/// Readers are buffered. Fields are assigned based on headers.
+/// If a record can't be deserialized it is ignored.
+/// With the `compression` feature, files are decompressed as they are read.
+///
+/// Returns the concatenated transactions, and the table of their sources.
+///
+/// # Errors
+/// A file can't be opened. All the files are opened before any is read.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(paths)))]
+#[cfg(feature = "async")]
+pub async fn txs_from_csv_files(
+    paths: &[impl AsRef<Path>],
+) -> Result<(BoxStream<'static, Transaction>, Sources), ()> {
+    let mut inputs = Vec::with_capacity(paths.len());
+    for path in paths {
+        let file = rt::open(path).await.map_err(|_e| ())?;
+        #[cfg(feature = "compression")]
+        let file = Compression::from_path(path).decoder(file);
+
+        let txs: BoxStream<_> = Box::pin(txs_with_lines_from_reader(file));
+        inputs.push((path.as_ref().to_path_buf(), txs));
+    }
+
+    Ok(txs_from_sources(inputs))
+}
+
+/// Expand the inputs into the files they name, in order. A directory names its files,
+/// sorted by name. A glob pattern names the matching files, sorted by path. Any other
+/// input names itself.
+///
+/// # Errors
+/// A directory can't be read, a pattern is invalid or matches no file.
+pub fn expand_paths(inputs: &[impl AsRef<Path>]) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for input in inputs {
+        let input = input.as_ref();
+        let pattern = input.to_string_lossy();
+
+        if input.is_dir() {
+            let mut files = fs::read_dir(input)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .filter(|path| path.as_ref().map_or(true, |path| path.is_file()))
+                .collect::<io::Result<Vec<_>>>()?;
+            files.sort_unstable();
+            paths.extend(files);
+        } else if pattern.contains(['*', '?', '[']) {
+            let matches = glob::glob(&pattern)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+                .filter(|path| path.as_ref().map_or(true, |path| path.is_file()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(io::Error::other)?;
+            if matches.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No file matches {pattern}"),
+                ));
+            }
+            paths.extend(matches);
+        } else {
+            paths.push(input.to_path_buf());
+        }
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "async")]
+    fn sources(lines: &[(usize, u64)]) -> Sources {
+        let sources = Sources::default();
+        sources.add_path(PathBuf::from("a.csv"));
+        sources.add_path(PathBuf::from("b.csv"));
+        for (path, line) in lines {
+            sources.push(*path, *line);
+        }
+
+        sources
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn consecutive_lines_share_a_run() {
+        let sources = sources(&[(0, 2), (0, 3), (0, 4), (1, 2), (1, 3)]);
+
+        assert_eq!(sources.0.lock().unwrap().runs.len(), 2);
+        assert_eq!(
+            sources.source(2),
+            Some(Source {
+                path: PathBuf::from("a.csv"),
+                line: 4
+            })
+        );
+        assert_eq!(
+            sources.source(4),
+            Some(Source {
+                path: PathBuf::from("b.csv"),
+                line: 3
+            })
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn skipped_lines_start_a_run() {
+        let sources = sources(&[(0, 2), (0, 5), (0, 6), (1, 7)]);
+
+        let lines = (0..4)
+            .map(|sequence| sources.source(sequence).unwrap().line)
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines, [2, 5, 6, 7]);
+        assert_eq!(sources.source(4), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn rejections_reference_their_source() {
+        use futures::io::Cursor;
+
+        use crate::{Broker, SequentialBroker};
+
+        let first = "type,client,tx,amount\ndeposit,1,1,1.0\nwithdraw,1,2,5.0\n";
+        let second = "type,client,tx,amount\nnot a record\n\ndeposit,1,3,1.0\nwithdraw,1,4,5.0\n";
+        let inputs = [("first.csv", first), ("second.csv", second)]
+            .into_iter()
+            .map(|(path, input)| {
+                let txs: BoxStream<_> =
+                    Box::pin(txs_with_lines_from_reader(Cursor::new(input.to_owned())));
+                (PathBuf::from(path), txs)
+            })
+            .collect();
+
+        crate::rt::block_on(async {
+            let (txs, sources) = txs_from_sources(inputs);
+            let output = SequentialBroker::default().process(txs).await;
+
+            let rejected = output
+                .rejections
+                .iter()
+                .map(|rejection| sources.source(rejection.sequence).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                rejected,
+                [
+                    Source {
+                        path: PathBuf::from("first.csv"),
+                        line: 3
+                    },
+                    Source {
+                        path: PathBuf::from("second.csv"),
+                        line: 5
+                    },
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn directories_expand_to_their_sorted_files() {
+        let dir = std::env::temp_dir().join(format!("expand_paths_{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        for name in ["b.csv", "a.csv", "c.jsonl"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let from_dir = expand_paths(&[&dir]).unwrap();
+        let from_glob = expand_paths(&[dir.join("*.csv")]).unwrap();
+        let no_match = expand_paths(&[dir.join("*.zst")]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            from_dir,
+            [dir.join("a.csv"), dir.join("b.csv"), dir.join("c.jsonl")]
+        );
+        assert_eq!(from_glob, [dir.join("a.csv"), dir.join("b.csv")]);
+        assert_eq!(no_match.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashSet, num::NonZeroU64, time::Duration};
+
+use account::{AccountId, AccountState, Transaction};
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+
+use crate::broker::AccountOrder;
+use crate::engine::Engine;
+use crate::rt;
+
+/// When the streaming broker emits the changed accounts. The changes are always emitted
+/// at the end of the input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Emission {
+    /// Emit after every given number of transactions.
+    pub transactions: Option<NonZeroU64>,
+    /// Emit after every given period, while the input is idle as well.
+    pub interval: Option<Duration>,
+}
+
+/// Accounts changed since the previous delta.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delta {
+    /// Number of transactions applied to the accounts.
+    pub sequence: u64,
+    /// States of the accounts opened or changed since the previous delta,
+    /// arranged by the broker's [`AccountOrder`].
+    pub accounts: Vec<AccountState>,
+}
+
+/// Applies the transactions one by one, emitting the changed accounts while the input
+/// is processed. Suited for unbounded input.
+#[derive(Debug, Clone, Default)]
+pub struct StreamingBroker {
+    pub order: AccountOrder,
+    pub emission: Emission,
+}
+
+impl StreamingBroker {
+    /// Process the transactions, emitting the changed accounts as deltas.
+    /// Applying the deltas in order mirrors the account states of the broker.
+    /// Empty deltas are not emitted.
+    pub fn deltas(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Send + 'static,
+    ) -> impl Stream<Item = Delta> {
+        let ticks: BoxStream<()> = match self.emission.interval {
+            Some(period) => rt::interval(period).boxed(),
+            None => stream::pending().boxed(),
+        };
+        let input = transactions
+            .map(Event::Transaction)
+            .chain(stream::iter([Event::InputEnd]));
+
+        let state = Changes {
+            events: stream::select(input, ticks.map(|()| Event::Tick)).boxed(),
+            engine: Engine::new(self.order),
+            order: self.order,
+            changed: Vec::new(),
+            changed_ids: HashSet::new(),
+            sequence: 0,
+            since_emission: 0,
+            every: self.emission.transactions,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            let delta = state.next_delta().await?;
+            Some((delta, state))
+        })
+    }
+}
+
+enum Event {
+    Transaction(Transaction),
+    Tick,
+    InputEnd,
+}
+
+/// Tracks the accounts changed since the last delta.
+struct Changes {
+    events: BoxStream<'static, Event>,
+    engine: Engine,
+    order: AccountOrder,
+    /// Changed account ids, in the order they first changed.
+    changed: Vec<AccountId>,
+    changed_ids: HashSet<AccountId>,
+    sequence: u64,
+    since_emission: u64,
+    every: Option<NonZeroU64>,
+}
+
+impl Changes {
+    /// Next non empty delta. Returns `None` once the input is exhausted.
+    async fn next_delta(&mut self) -> Option<Delta> {
+        while let Some(event) = self.events.next().await {
+            match event {
+                Event::Transaction(transaction) => {
+                    self.apply(transaction);
+
+                    let due = self
+                        .every
+                        .is_some_and(|every| self.since_emission >= every.get());
+                    if due {
+                        if let Some(delta) = self.take_delta() {
+                            return Some(delta);
+                        }
+                    }
+                }
+                Event::Tick => {
+                    if let Some(delta) = self.take_delta() {
+                        return Some(delta);
+                    }
+                }
+                Event::InputEnd => {
+                    // the input is not polled after it ends
+                    self.events = stream::empty().boxed();
+                    return self.take_delta();
+                }
+            }
+        }
+
+        None
+    }
+
+    fn apply(&mut self, transaction: Transaction) {
+        let account_id = transaction.target_account_id.clone();
+        let is_new = self.engine.account(&account_id).is_none();
+
+        // rejected transactions leave the account unchanged
+        let _res = self.engine.apply(transaction);
+
+        #[cfg(feature = "tracing")]
+        if _res.is_err() {
+            tracing::info!(err = ?_res, "Transaction rejected");
+        }
+
+        if (is_new || _res.is_ok()) && self.changed_ids.insert(account_id.clone()) {
+            self.changed.push(account_id);
+        }
+
+        self.sequence += 1;
+        self.since_emission += 1;
+    }
+
+    fn take_delta(&mut self) -> Option<Delta> {
+        self.since_emission = 0;
+        if self.changed.is_empty() {
+            return None;
+        }
+
+        self.changed_ids.clear();
+        let accounts = self
+            .order
+            .arrange(std::mem::take(&mut self.changed))
+            .into_iter()
+            .map(|account_id| {
+                self.engine
+                    .account(&account_id)
+                    .expect("Every changed account must be present")
+                    .clone()
+            })
+            .collect();
+
+        Some(Delta {
+            sequence: self.sequence,
+            accounts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use account::{Amount, TransactionId};
+    use async_channel as channel;
+
+    use super::*;
+
+    fn deposit(account_id: u16, tx_id: u32) -> Transaction {
+        Transaction::deposit(
+            AccountId(account_id),
+            TransactionId(tx_id),
+            Amount::from_u64(1),
+        )
+        .unwrap()
+    }
+
+    fn withdraw(account_id: u16, tx_id: u32) -> Transaction {
+        Transaction::withdraw(
+            AccountId(account_id),
+            TransactionId(tx_id),
+            Amount::from_u64(1),
+        )
+        .unwrap()
+    }
+
+    fn every(transactions: u64) -> StreamingBroker {
+        StreamingBroker {
+            emission: Emission {
+                transactions: NonZeroU64::new(transactions),
+                interval: None,
+            },
+            ..StreamingBroker::default()
+        }
+    }
+
+    /// Sequence number and account ids of every delta.
+    fn ids(broker: &StreamingBroker, transactions: Vec<Transaction>) -> Vec<(u64, Vec<u16>)> {
+        let deltas = broker.deltas(stream::iter(transactions));
+        rt::block_on(deltas.collect::<Vec<_>>())
+            .into_iter()
+            .map(|delta| {
+                let ids = delta.accounts.iter().map(|state| state.id.0).collect();
+                (delta.sequence, ids)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deltas_are_emitted_every_n_transactions() {
+        let transactions = [1, 2, 1, 3, 3]
+            .into_iter()
+            .zip(0..)
+            .map(|(account_id, tx_id)| deposit(account_id, tx_id))
+            .collect();
+
+        assert_eq!(
+            ids(&every(2), transactions),
+            [(2, vec![1, 2]), (4, vec![1, 3]), (5, vec![3])]
+        );
+    }
+
+    #[test]
+    fn changes_are_emitted_at_the_end_of_the_input() {
+        let transactions = vec![deposit(2, 0), deposit(1, 1), deposit(2, 2)];
+
+        assert_eq!(
+            ids(&StreamingBroker::default(), transactions),
+            [(3, vec![1, 2])]
+        );
+    }
+
+    #[test]
+    fn rejected_transactions_do_not_change_accounts() {
+        let transactions = vec![deposit(1, 0), withdraw(1, 1), withdraw(1, 2)];
+
+        assert_eq!(ids(&every(1), transactions), [(1, vec![1]), (2, vec![1])]);
+    }
+
+    #[test]
+    fn accounts_opened_by_rejected_transactions_are_emitted() {
+        let transactions = vec![withdraw(1, 0)];
+        let deltas = every(1).deltas(stream::iter(transactions));
+
+        let deltas = rt::block_on(deltas.collect::<Vec<_>>());
+
+        assert_eq!(
+            deltas,
+            [Delta {
+                sequence: 1,
+                accounts: vec![AccountState::from_id(AccountId(1))],
+            }]
+        );
+    }
+
+    #[test]
+    fn deltas_are_emitted_while_the_input_is_idle() {
+        let broker = StreamingBroker {
+            emission: Emission {
+                transactions: None,
+                interval: Some(Duration::from_millis(10)),
+            },
+            ..StreamingBroker::default()
+        };
+        let (sender, receiver) = channel::unbounded();
+        let mut deltas = Box::pin(broker.deltas(receiver));
+
+        rt::block_on(async {
+            sender.send(deposit(1, 0)).await.unwrap();
+            let delta = deltas.next().await.unwrap();
+            assert_eq!(delta.sequence, 1);
+
+            sender.send(deposit(2, 1)).await.unwrap();
+            drop(sender);
+            let delta = deltas.next().await.unwrap();
+            assert_eq!(delta.sequence, 2);
+            assert_eq!(delta.accounts[0].id, AccountId(2));
+
+            assert_eq!(deltas.next().await, None);
+        });
+    }
+}
@@ -0,0 +1,304 @@
+use std::io::{self, Write};
+
+use account::{AccountState, Amount, IsLocked};
+use tabwriter::{Alignment, TabWriter};
+
+use crate::broker::Rejection;
+use crate::sources::Sources;
+
+/// Terminal escape code coloring the rows of locked accounts.
+const LOCKED_COLOR: &str = "\x1b[31m";
+/// Terminal escape code resetting the color.
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// Human readable table with aligned columns. Amounts are written with exactly
+/// [`Amount::DECIMAL_POINTS`] decimals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Table {
+    /// End the table with a row of the column totals.
+    pub totals: bool,
+    /// Color the rows of locked accounts. Meant for terminals, the colors are
+    /// written as escape codes.
+    pub color: bool,
+}
+
+impl Table {
+    /// Write the account states into the writer, in the order they are received.
+    /// Amount columns are right aligned. The totals row holds the number of locked accounts.
+    ///
+    /// # Errors
+    /// Writing the table fails.
+    pub fn accounts_into_writer(
+        self,
+        writer: impl Write,
+        account_states: impl IntoIterator<Item = AccountState>,
+    ) -> io::Result<()> {
+        let mut tw = TabWriter::new(writer)
+            .padding(2)
+            .alignment(Alignment::Right)
+            .ansi(self.color);
+        let mut available = 0;
+        let mut held = 0;
+        let mut locked = 0;
+
+        writeln!(tw, "client\tavailable\theld\ttotal\tlocked")?;
+        for state in account_states {
+            let total = state
+                .total()
+                .map_or_else(|_e| "overflow".to_owned(), |total| total.to_fixed_string());
+            let is_locked = state.is_locked == IsLocked::Locked;
+            let (color, reset) = match self.color && is_locked {
+                true => (LOCKED_COLOR, RESET_COLOR),
+                false => ("", ""),
+            };
+
+            writeln!(
+                tw,
+                "{color}{}\t{}\t{}\t{total}\t{is_locked}{reset}",
+                state.id.0,
+                state.available.to_fixed_string(),
+                state.held.to_fixed_string(),
+            )?;
+
+            available += state.available.to_fixed_point();
+            held += state.held.to_fixed_point();
+            locked += usize::from(is_locked);
+        }
+
+        if self.totals {
+            writeln!(
+                tw,
+                "total\t{}\t{}\t{}\t{locked}",
+                Amount::fixed_point_to_string(available),
+                Amount::fixed_point_to_string(held),
+                Amount::fixed_point_to_string(available + held),
+            )?;
+        }
+
+        tw.flush()
+    }
+
+    /// Write the rejected transactions into the writer, in the order they are received.
+    /// Columns are left aligned. The totals row holds the sum of the rejected amounts.
+    ///
+    /// # Errors
+    /// Writing the table fails.
+    pub fn rejections_into_writer<'a>(
+        self,
+        writer: impl Write,
+        rejections: impl IntoIterator<Item = &'a Rejection>,
+    ) -> io::Result<()> {
+        self.write_rejections(writer, rejections, None)
+    }
+
+    /// Write the rejected transactions into the writer, in the order they are received,
+    /// with the file and line they were read from.
+    ///
+    /// # Errors
+    /// Writing the table fails.
+    pub fn rejections_with_sources_into_writer<'a>(
+        self,
+        writer: impl Write,
+        rejections: impl IntoIterator<Item = &'a Rejection>,
+        sources: &Sources,
+    ) -> io::Result<()> {
+        self.write_rejections(writer, rejections, Some(sources))
+    }
+
+    fn write_rejections<'a>(
+        self,
+        writer: impl Write,
+        rejections: impl IntoIterator<Item = &'a Rejection>,
+        sources: Option<&Sources>,
+    ) -> io::Result<()> {
+        let mut tw = TabWriter::new(writer).padding(2);
+        let mut count = 0;
+        let mut amount_sum = 0;
+
+        let source_columns = if sources.is_some() {
+            "file\tline\t"
+        } else {
+            ""
+        };
+        writeln!(
+            tw,
+            "sequence\t{source_columns}client\ttype\ttx\tamount\terror"
+        )?;
+        for rejection in rejections {
+            let (kind, tx_id, amount) = rejection.transaction.kind.fields();
+            let source = match sources.map(|sources| sources.source(rejection.sequence)) {
+                Some(Some(source)) => format!("{}\t{}\t", source.path.display(), source.line),
+                Some(None) => "\t\t".to_owned(),
+                None => String::new(),
+            };
+
+            writeln!(
+                tw,
+                "{}\t{source}{}\t{kind}\t{}\t{}\t{}",
+                rejection.sequence,
+                rejection.transaction.target_account_id.0,
+                tx_id.0,
+                amount.map(Amount::to_fixed_string).unwrap_or_default(),
+                rejection.error,
+            )?;
+
+            count += 1;
+            amount_sum += amount.map_or(0, Amount::to_fixed_point);
+        }
+
+        if self.totals {
+            let source_columns = if sources.is_some() { "\t\t" } else { "" };
+            writeln!(
+                tw,
+                "total\t{source_columns}\t\t\t{}\t{count} rejected",
+                Amount::fixed_point_to_string(amount_sum)
+            )?;
+        }
+
+        tw.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use account::{AccountId, Error, Transaction, TransactionId};
+
+    use super::*;
+
+    fn states() -> Vec<AccountState> {
+        vec![
+            AccountState {
+                id: AccountId(2),
+                available: Amount::from_fixed_point(105, 1).unwrap(),
+                held: Amount::MIN,
+                is_locked: IsLocked::Unlocked,
+            },
+            AccountState {
+                id: AccountId(300),
+                available: Amount::MIN,
+                held: Amount::from_u64(3),
+                is_locked: IsLocked::Locked,
+            },
+        ]
+    }
+
+    fn accounts_table(table: Table) -> String {
+        let mut output = Vec::new();
+        table.accounts_into_writer(&mut output, states()).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn accounts_are_aligned() {
+        assert_eq!(
+            accounts_table(Table::default()),
+            "\
+client  available    held    total  locked
+     2    10.5000  0.0000  10.5000  false
+   300     0.0000  3.0000   3.0000  true
+"
+        );
+    }
+
+    #[test]
+    fn accounts_totals_row() {
+        let table = accounts_table(Table {
+            totals: true,
+            color: false,
+        });
+
+        assert_eq!(
+            table.lines().last(),
+            Some(" total    10.5000  3.0000  13.5000  1")
+        );
+    }
+
+    #[test]
+    fn locked_accounts_are_colored() {
+        let table = accounts_table(Table {
+            totals: false,
+            color: true,
+        });
+        let lines = table.lines().collect::<Vec<_>>();
+
+        assert!(!lines[1].contains(LOCKED_COLOR));
+        assert!(lines[2].starts_with(&format!("   {LOCKED_COLOR}300")));
+        assert!(lines[2].ends_with(&format!("true{RESET_COLOR}")));
+    }
+
+    fn rejections() -> [Rejection; 2] {
+        [
+            Rejection {
+                sequence: 3,
+                transaction: Transaction::withdraw(
+                    AccountId(1),
+                    TransactionId(12),
+                    Amount::from_u64(20),
+                )
+                .unwrap(),
+                error: Error::InsufficientFundsForWithdraw,
+            },
+            Rejection {
+                sequence: 10,
+                transaction: Transaction::dispute(AccountId(2), TransactionId(7)),
+                error: Error::InvalidDisputeTarget,
+            },
+        ]
+    }
+
+    #[test]
+    fn rejections_are_aligned() {
+        let mut output = Vec::new();
+
+        Table {
+            totals: true,
+            color: false,
+        }
+        .rejections_into_writer(&mut output, &rejections())
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+sequence  client  type      tx  amount   error
+3         1       withdraw  12  20.0000  Not enough available funds to make a withdraw
+10        2       dispute   7            Target transaction id is not present in the set of all deposits
+total                           20.0000  2 rejected
+"
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn rejections_reference_their_source() {
+        use futures::{io::Cursor, stream::BoxStream, StreamExt};
+
+        use crate::{sources::txs_from_sources, txs_with_lines_from_reader};
+
+        let input = "type,client,tx,amount\ndeposit,1,1,1.0\n\ndeposit,1,2,1.0\n";
+        let txs: BoxStream<_> = Box::pin(txs_with_lines_from_reader(Cursor::new(input)));
+        let mut output = Vec::new();
+
+        crate::rt::block_on(async {
+            let (txs, sources) = txs_from_sources(vec![("day1.csv".into(), txs)]);
+            assert_eq!(txs.count().await, 2);
+
+            let mut rejections = rejections();
+            rejections[0].sequence = 1;
+            rejections[1].sequence = 10;
+            Table::default()
+                .rejections_with_sources_into_writer(&mut output, &rejections, &sources)
+                .unwrap();
+        });
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+sequence  file      line  client  type      tx  amount   error
+1         day1.csv  4     1       withdraw  12  20.0000  Not enough available funds to make a withdraw
+10                        2       dispute   7            Target transaction id is not present in the set of all deposits
+"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use account::Error;
+#[cfg(feature = "async")]
+use account::{AccountEvents, Amount, Transaction, TransactionKind};
+
+/// Count of transactions and the sum of their amounts. The sum is a fixed point number
+/// at [`account::Amount::DECIMAL_POINTS`] scale, it may exceed [`account::Amount::MAX`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub count: u64,
+    pub amount: u128,
+}
+
+#[cfg(feature = "async")]
+impl Tally {
+    fn add(&mut self, amount: Option<&Amount>) {
+        self.count += 1;
+        self.amount += amount.map_or(0, Amount::to_fixed_point);
+    }
+
+    fn merge(&mut self, other: Tally) {
+        self.count += other.count;
+        self.amount += other.amount;
+    }
+}
+
+/// Applied and rejected transactions of a single type.
+///
+/// Applied disputes, resolutions and charge backs are summed by the amount of the
+/// disputed deposit. Rejected ones carry no amount.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeTally {
+    pub applied: Tally,
+    pub rejected: Tally,
+}
+
+/// Tallies of the processed transactions, by transaction type and by rejection error.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tallies {
+    pub deposits: TypeTally,
+    pub withdrawals: TypeTally,
+    pub disputes: TypeTally,
+    pub resolves: TypeTally,
+    pub chargebacks: TypeTally,
+    /// Rejected transactions of all types, by the error that rejected them.
+    pub errors: BTreeMap<Error, Tally>,
+}
+
+#[cfg(feature = "async")]
+impl Tallies {
+    /// Record the result of applying the transaction.
+    pub(crate) fn record(
+        &mut self,
+        transaction: &Transaction,
+        result: &Result<AccountEvents, Error>,
+    ) {
+        let tally = match &transaction.kind {
+            TransactionKind::Deposit(_) => &mut self.deposits,
+            TransactionKind::Withdraw(_) => &mut self.withdrawals,
+            TransactionKind::Dispute(_) => &mut self.disputes,
+            TransactionKind::Resolve(_) => &mut self.resolves,
+            TransactionKind::ChargeBack(_) => &mut self.chargebacks,
+        };
+        let (_, _, amount) = transaction.kind.fields();
+
+        match result {
+            Ok(events) => tally.applied.add(events.event().kind.amount()),
+            Err(error) => {
+                tally.rejected.add(amount);
+                self.errors.entry(error.clone()).or_default().add(amount);
+            }
+        }
+    }
+
+    /// Add the tallies of another part of the input.
+    pub(crate) fn merge(&mut self, other: Tallies) {
+        for (tally, other) in [
+            (&mut self.deposits, other.deposits),
+            (&mut self.withdrawals, other.withdrawals),
+            (&mut self.disputes, other.disputes),
+            (&mut self.resolves, other.resolves),
+            (&mut self.chargebacks, other.chargebacks),
+        ] {
+            tally.applied.merge(other.applied);
+            tally.rejected.merge(other.rejected);
+        }
+
+        for (error, other) in other.errors {
+            self.errors.entry(error).or_default().merge(other);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use account::{Account, AccountId, TransactionId};
+
+    use super::*;
+
+    #[test]
+    fn tallies_are_recorded_by_type_and_error() {
+        let mut account = Account::from_id(AccountId(1));
+        let mut tallies = Tallies::default();
+
+        for transaction in [
+            Transaction::deposit(AccountId(1), TransactionId(0), Amount::from_u64(10)).unwrap(),
+            Transaction::withdraw(AccountId(1), TransactionId(1), Amount::from_u64(20)).unwrap(),
+            Transaction::dispute(AccountId(1), TransactionId(0)),
+            Transaction::dispute(AccountId(1), TransactionId(0)),
+            Transaction::resolve(AccountId(1), TransactionId(7)),
+        ] {
+            let result = account.try_apply_transaction(transaction.clone());
+            tallies.record(&transaction, &result);
+        }
+
+        let tally = |count, amount: u128| Tally {
+            count,
+            amount: amount * 10_000,
+        };
+        assert_eq!(tallies.deposits.applied, tally(1, 10));
+        assert_eq!(tallies.withdrawals.rejected, tally(1, 20));
+        assert_eq!(tallies.disputes.applied, tally(1, 10));
+        assert_eq!(tallies.disputes.rejected, tally(1, 0));
+        assert_eq!(tallies.resolves.rejected, tally(1, 0));
+        assert_eq!(
+            tallies.errors.into_iter().collect::<Vec<_>>(),
+            [
+                (Error::InsufficientFundsForWithdraw, tally(1, 20)),
+                (Error::InvalidResolveTarget, tally(1, 0)),
+                (Error::AlreadyDisputed, tally(1, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_tallies_are_summed() {
+        let mut account = Account::from_id(AccountId(1));
+        let mut tallies = Tallies::default();
+        for transaction in [
+            Transaction::deposit(AccountId(1), TransactionId(0), Amount::from_u64(10)).unwrap(),
+            Transaction::withdraw(AccountId(1), TransactionId(1), Amount::from_u64(20)).unwrap(),
+        ] {
+            let result = account.try_apply_transaction(transaction.clone());
+            tallies.record(&transaction, &result);
+        }
+
+        let mut merged = tallies.clone();
+        merged.merge(tallies);
+
+        assert_eq!(merged.deposits.applied.count, 2);
+        assert_eq!(merged.withdrawals.rejected.count, 2);
+        assert_eq!(
+            merged.errors[&Error::InsufficientFundsForWithdraw].amount,
+            400_000
+        );
+    }
+}
@@ -1,82 +1,320 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, future::Future, pin::pin};
 
 use account::{Account, AccountId, AccountState, Transaction};
 
-use async_std::{
-    channel::{self, Receiver, Sender},
-    prelude::*,
-    stream,
-    task::{self, JoinHandle},
+use async_channel::{Receiver, Sender};
+use futures::{
+    stream::{self, FuturesOrdered},
+    Stream, StreamExt,
 };
-use futures::{stream::FuturesUnordered, StreamExt};
-#[cfg(feature = "tracing")]
-use tracing;
+
+use crate::backpressure::{Backpressure, InFlight};
+use crate::broker::{
+    forward_events, try_apply, AccountOrder, Broker, BrokerOutput, Rejection, SequencedEvent,
+};
+use crate::broker_handle::{BrokerHandle, Request};
+use crate::engine::Engine;
+use crate::rt::{self, JoinHandle};
+use crate::tally::Tallies;
 
 /// This error should never happen. This must be satisfied by inspection.
 const CLOSED_CHANNEL_ERROR: &str = "Existing accounts must have open channels";
 
+/// Applies the transactions one by one, in the order they are received.
+#[derive(Debug, Clone, Default)]
+pub struct SequentialBroker {
+    pub order: AccountOrder,
+}
+
+impl Broker for SequentialBroker {
+    fn process(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> impl Future<Output = BrokerOutput> {
+        transaction_broker_sync_with_events(transactions, self.order, None)
+    }
+
+    fn process_with_events(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> (
+        impl Stream<Item = SequencedEvent>,
+        impl Future<Output = BrokerOutput>,
+    ) {
+        let (events, receiver) = async_channel::unbounded();
+        let output = transaction_broker_sync_with_events(transactions, self.order, Some(events));
+
+        (receiver, output)
+    }
+}
+
+/// Runs every account as a separate task. Transactions are dispatched to the accounts
+/// over channels, in the order they are received.
+#[derive(Debug, Clone, Default)]
+pub struct ActorBroker {
+    pub order: AccountOrder,
+    pub backpressure: Backpressure,
+}
+
+impl ActorBroker {
+    /// Start processing the transactions. Returns a handle to query the accounts
+    /// while the broker runs, and the broker future.
+    pub fn start(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> (BrokerHandle, impl Future<Output = BrokerOutput>) {
+        self.start_with_events(transactions, None)
+    }
+
+    fn start_with_events(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+        events: Option<Sender<SequencedEvent>>,
+    ) -> (BrokerHandle, impl Future<Output = BrokerOutput>) {
+        let (handle, requests) = BrokerHandle::new();
+        let order = self.order;
+        let backpressure = self.backpressure.clone();
+
+        let output = async move {
+            transaction_broker_with_events(transactions, order, &backpressure, requests, events)
+                .await
+        };
+
+        (handle, output)
+    }
+}
+
+impl Broker for ActorBroker {
+    fn process(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> impl Future<Output = BrokerOutput> {
+        let (_handle, output) = self.start(transactions);
+        output
+    }
+
+    fn process_with_events(
+        &self,
+        transactions: impl Stream<Item = Transaction> + Unpin,
+    ) -> (
+        impl Stream<Item = SequencedEvent>,
+        impl Future<Output = BrokerOutput>,
+    ) {
+        let (events, receiver) = async_channel::unbounded();
+        let (_handle, output) = self.start_with_events(transactions, Some(events));
+
+        (receiver, output)
+    }
+}
+
+/// Messages handled by the account tasks, in the order they are received.
+#[derive(Debug)]
+enum AccountMessage {
+    Transaction(u64, Transaction),
+    /// Reply with the current account state.
+    Query(Sender<AccountState>),
+}
+
+/// Events handled by the broker, in the order they are received.
+enum BrokerEvent {
+    Transaction(Transaction),
+    Request(Request),
+    InputEnd,
+}
+
 #[derive(Debug)]
 struct AccountHandler {
-    sender: Sender<Transaction>,
-    handler: JoinHandle<Account>,
+    sender: Sender<AccountMessage>,
+    handler: JoinHandle<(Account, Vec<Rejection>, Tallies)>,
+    in_flight: InFlight,
 }
 
+/// Apply the transactions one by one, in the order they are received. Returns the final
+/// account states, by ascending client id.
+#[deprecated(note = "Use `SequentialBroker` through the `Broker` trait")]
 pub async fn transaction_broker_sync(
-    mut transaction_requests: impl Stream<Item = Transaction> + Unpin,
+    transaction_requests: impl Stream<Item = Transaction> + Unpin,
 ) -> impl Stream<Item = AccountState> {
-    let mut accounts: HashMap<AccountId, Account> = HashMap::new();
+    let output = SequentialBroker::default()
+        .process(transaction_requests)
+        .await;
+    stream::iter(output.accounts)
+}
+
+/// Run every account as a separate task. Returns the final account states, by ascending
+/// client id.
+#[deprecated(note = "Use `ActorBroker` through the `Broker` trait")]
+pub async fn transaction_broker(
+    transaction_requests: impl Stream<Item = Transaction> + Unpin,
+) -> impl Stream<Item = AccountState> {
+    let output = ActorBroker::default().process(transaction_requests).await;
+    stream::iter(output.accounts)
+}
+
+async fn transaction_broker_sync_with_events(
+    mut transaction_requests: impl Stream<Item = Transaction> + Unpin,
+    order: AccountOrder,
+    events: Option<Sender<SequencedEvent>>,
+) -> BrokerOutput {
+    let mut engine = Engine::new(order);
+    let mut rejections = Vec::new();
+    let mut tallies = Tallies::default();
+    let mut sequence = 0;
 
     while let Some(tx_request) = transaction_requests.next().await {
-        if let Some(account) = accounts.get_mut(&tx_request.target_account_id) {
-            let _res = account.try_apply_transaction(tx_request);
-        } else {
-            let account_id = tx_request.target_account_id.clone();
-            let mut new_account = Account::from_id(account_id.clone());
-            let _res = new_account.try_apply_transaction(tx_request);
-            accounts.insert(account_id, new_account);
+        let result = engine.apply(tx_request.clone());
+        tallies.record(&tx_request, &result);
+
+        match result {
+            Ok(account_events) => forward_events(events.as_ref(), sequence, account_events),
+            Err(error) => rejections.push(Rejection {
+                sequence,
+                transaction: tx_request,
+                error,
+            }),
         }
+        sequence += 1;
     }
 
-    stream::from_iter(accounts.into_values()).map(Account::into_state)
+    BrokerOutput {
+        accounts: engine.into_states(),
+        rejections,
+        tallies,
+    }
 }
 
 #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
-pub async fn transaction_broker(
-    mut transaction_requests: impl Stream<Item = Transaction> + Unpin,
-) -> impl Stream<Item = AccountState> {
+async fn transaction_broker_with_events(
+    transaction_requests: impl Stream<Item = Transaction> + Unpin,
+    order: AccountOrder,
+    backpressure: &Backpressure,
+    requests: Receiver<Request>,
+    account_events: Option<Sender<SequencedEvent>>,
+) -> BrokerOutput {
     let mut account_handlers: HashMap<AccountId, AccountHandler> = HashMap::new();
+    let mut first_seen = Vec::new();
+    let mut sequence = 0;
+    let in_flight = backpressure.in_flight();
+    let pending_requests = requests.clone();
+
+    // requests are handled between transactions, the broker stops at the end of the input
+    let input = transaction_requests
+        .map(BrokerEvent::Transaction)
+        .chain(stream::iter([BrokerEvent::InputEnd]));
+    let mut events = pin!(stream::select(input, requests.map(BrokerEvent::Request)));
 
     // Note: sequentially handling the input stream. Assignment defined transaction
     // order to be the csv item order.
-    while let Some(tx_request) = transaction_requests.next().await {
+    while let Some(event) = events.next().await {
+        let tx_request = match event {
+            BrokerEvent::Transaction(tx_request) => tx_request,
+            BrokerEvent::Request(request) => {
+                handle_request(request, &account_handlers, &first_seen, order, sequence).await;
+                continue;
+            }
+            BrokerEvent::InputEnd => break,
+        };
+
         if let Some(account_handler) = account_handlers.get(&tx_request.target_account_id) {
-            send_tx(account_handler, tx_request).await;
+            send_tx(account_handler, sequence, tx_request).await;
         } else {
-            let account_handler = start_account_handler(tx_request.clone());
-            send_tx(&account_handler, tx_request.clone()).await;
+            let account_handler = start_account_handler(
+                tx_request.clone(),
+                backpressure,
+                in_flight.clone(),
+                account_events.clone(),
+            );
+            send_tx(&account_handler, sequence, tx_request.clone()).await;
+            first_seen.push(tx_request.target_account_id.clone());
             account_handlers.insert(tx_request.target_account_id, account_handler);
         }
+        sequence += 1;
     }
 
-    join_account_handlers(account_handlers)
+    // dropping the pending requests ends the queries still waiting for a reply
+    pending_requests.close();
+    while pending_requests.try_recv().is_ok() {}
+
+    join_account_handlers(account_handlers, order.arrange(first_seen)).await
 }
 
-fn start_account_handler(tx_request: Transaction) -> AccountHandler {
-    // Note: using unbounded channels for convenience.
-    // In practice account we could control how much throughput is
-    // allowed per account.
-    let (sender, receiver) = channel::unbounded();
-    let handler = task::spawn(transaction_listener(tx_request.target_account_id, receiver));
+/// Query the account tasks. Queries are queued behind the transactions already sent
+/// to the accounts, so the replies reflect all of them. Replies are not awaited.
+///
+/// # Errors
+/// Requests of dropped handles and queries to closed channels are ignored.
+/// Debug builds will panic on closed account channels.
+async fn handle_request(
+    request: Request,
+    account_handlers: &HashMap<AccountId, AccountHandler>,
+    first_seen: &[AccountId],
+    order: AccountOrder,
+    sequence: u64,
+) {
+    match request {
+        Request::Account(account_id, reply) => {
+            let state = match account_handlers.get(&account_id) {
+                Some(account_handler) => Some(query(account_handler).await),
+                None => None,
+            };
+            let _res = reply.try_send(state);
+        }
+        Request::Snapshot(reply) => {
+            let mut states = Vec::with_capacity(first_seen.len());
+            for account_id in order.arrange(first_seen.to_vec()) {
+                states.push(query(&account_handlers[&account_id]).await);
+            }
+            let _res = reply.try_send((sequence, states));
+        }
+    }
+}
+
+async fn query(account: &AccountHandler) -> Receiver<AccountState> {
+    let (reply, state) = async_channel::bounded(1);
+    let _res = account.sender.send(AccountMessage::Query(reply)).await;
+
+    #[cfg(feature = "tracing")]
+    if _res.is_err() {
+        tracing::error!(error = %CLOSED_CHANNEL_ERROR);
+    }
 
-    AccountHandler { sender, handler }
+    debug_assert!(_res.is_ok(), "{}", CLOSED_CHANNEL_ERROR);
+
+    state
+}
+
+fn start_account_handler(
+    tx_request: Transaction,
+    backpressure: &Backpressure,
+    in_flight: InFlight,
+    events: Option<Sender<SequencedEvent>>,
+) -> AccountHandler {
+    let (sender, receiver) = backpressure.account_channel();
+    let handler = rt::spawn(transaction_listener(
+        tx_request.target_account_id,
+        receiver,
+        in_flight.clone(),
+        events,
+    ));
+
+    AccountHandler {
+        sender,
+        handler,
+        in_flight,
+    }
 }
 
 /// # Errors
 /// Trying to send to a closed channel is silently ignored.
 /// Debug builds will panic.
 #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
-async fn send_tx(account: &AccountHandler, tx_request: Transaction) {
-    let _res = account.sender.send(tx_request).await;
+async fn send_tx(account: &AccountHandler, sequence: u64, tx_request: Transaction) {
+    account.in_flight.acquire().await;
+    let _res = account
+        .sender
+        .send(AccountMessage::Transaction(sequence, tx_request))
+        .await;
+    account.in_flight.record_queue_depth(account.sender.len());
 
     #[cfg(feature = "tracing")]
     if _res.is_err() {
@@ -86,16 +324,22 @@ async fn send_tx(account: &AccountHandler, tx_request: Transaction) {
     debug_assert!(matches!(_res, Ok(())), "{}", CLOSED_CHANNEL_ERROR);
 }
 
+/// Close all account channels and join the handlers in the given account order.
+///
 /// # Errors
 /// All handlers are expected to have open channels. Already closed channels
 /// are ignored. Debug builds will panic.
 #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
-fn join_account_handlers(
-    account_handlers: HashMap<AccountId, AccountHandler>,
-) -> impl Stream<Item = AccountState> {
-    account_handlers
+async fn join_account_handlers(
+    mut account_handlers: HashMap<AccountId, AccountHandler>,
+    account_order: Vec<AccountId>,
+) -> BrokerOutput {
+    let mut handlers = account_order
         .into_iter()
-        .map(|(_, account_handler)| {
+        .map(|account_id| {
+            let account_handler = account_handlers
+                .remove(&account_id)
+                .expect("Every seen account must have a handler");
             let _closed = account_handler.sender.close();
 
             #[cfg(feature = "tracing")]
@@ -107,24 +351,307 @@ fn join_account_handlers(
 
             account_handler.handler
         })
-        .collect::<FuturesUnordered<_>>()
-        .map(Account::into_state)
+        .collect::<FuturesOrdered<_>>();
+
+    let mut accounts = Vec::with_capacity(handlers.len());
+    let mut rejections = Vec::new();
+    let mut tallies = Tallies::default();
+
+    while let Some((account, account_rejections, account_tallies)) = handlers.next().await {
+        accounts.push(account.into_state());
+        rejections.extend(account_rejections);
+        tallies.merge(account_tallies);
+    }
+
+    rejections.sort_unstable_by_key(|rejection| rejection.sequence);
+
+    BrokerOutput {
+        accounts,
+        rejections,
+        tallies,
+    }
 }
 
-#[cfg_attr(feature = "tracing", tracing::instrument(skip(receiver), ret))]
-async fn transaction_listener(account_id: AccountId, receiver: Receiver<Transaction>) -> Account {
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(receiver, in_flight, events), ret)
+)]
+async fn transaction_listener(
+    account_id: AccountId,
+    receiver: Receiver<AccountMessage>,
+    in_flight: InFlight,
+    events: Option<Sender<SequencedEvent>>,
+) -> (Account, Vec<Rejection>, Tallies) {
     let mut account_aggregate = Account::from_id(account_id);
+    let mut rejections = Vec::new();
+    let mut tallies = Tallies::default();
     #[cfg(feature = "tracing")]
     tracing::info!("Opening the account");
 
-    while let Ok(tx_request) = receiver.recv().await {
-        #[cfg(feature = "tracing")]
-        tracing::info!(request = ?tx_request, "Transaction request received");
-        let _res = account_aggregate.try_apply_transaction(tx_request);
+    while let Ok(message) = receiver.recv().await {
+        match message {
+            AccountMessage::Transaction(sequence, tx_request) => {
+                #[cfg(feature = "tracing")]
+                tracing::info!(request = ?tx_request, "Transaction request received");
+                rejections.extend(try_apply(
+                    &mut account_aggregate,
+                    sequence,
+                    tx_request,
+                    events.as_ref(),
+                    &mut tallies,
+                ));
+                in_flight.release();
+            }
+            AccountMessage::Query(reply) => {
+                // the querying handle may be gone
+                let _res = reply.try_send(account_aggregate.state().clone());
+            }
+        }
     }
 
     #[cfg(feature = "tracing")]
     tracing::info!("Transaction listener closing");
 
-    account_aggregate
+    (account_aggregate, rejections, tallies)
+}
+
+#[cfg(test)]
+mod tests {
+    use account::{AccountEventKind, Amount, TransactionId};
+    use futures::{future, stream};
+
+    use super::*;
+    use crate::rt;
+
+    fn deposits(account_ids: &[u16]) -> impl Stream<Item = Transaction> + Unpin {
+        stream::iter(deposit_txs(account_ids))
+    }
+
+    fn deposit_txs(account_ids: &[u16]) -> Vec<Transaction> {
+        account_ids
+            .iter()
+            .enumerate()
+            .map(|(tx_id, account_id)| {
+                Transaction::deposit(
+                    AccountId(*account_id),
+                    TransactionId(tx_id as u32),
+                    Amount::from_u64(1),
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn ids(output: BrokerOutput) -> Vec<u16> {
+        output.accounts.iter().map(|state| state.id.0).collect()
+    }
+
+    #[test]
+    fn accounts_ordered_by_client_id() {
+        rt::block_on(async {
+            let input = [5, 3, 9, 3, 1, 5, 0];
+            let order = AccountOrder::ClientId;
+
+            let states = ActorBroker::default().process(deposits(&input)).await;
+            assert_eq!(ids(states), vec![0, 1, 3, 5, 9]);
+
+            let states = SequentialBroker { order }.process(deposits(&input)).await;
+            assert_eq!(ids(states), vec![0, 1, 3, 5, 9]);
+        })
+    }
+
+    #[test]
+    fn accounts_ordered_by_first_seen() {
+        rt::block_on(async {
+            let input = [5, 3, 9, 3, 1, 5, 0];
+            let order = AccountOrder::FirstSeen;
+
+            let states = ActorBroker {
+                order,
+                ..ActorBroker::default()
+            }
+            .process(deposits(&input))
+            .await;
+            assert_eq!(ids(states), vec![5, 3, 9, 1, 0]);
+
+            let states = SequentialBroker { order }.process(deposits(&input)).await;
+            assert_eq!(ids(states), vec![5, 3, 9, 1, 0]);
+        })
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_functions_wrap_the_brokers() {
+        rt::block_on(async {
+            let input = [5, 3, 9, 3, 1, 5, 0];
+            let order = AccountOrder::FirstSeen;
+            let expected = SequentialBroker { order }.process(deposits(&input)).await;
+
+            let sharded = crate::transaction_broker_sharded(
+                deposits(&input),
+                order,
+                crate::default_workers(),
+            )
+            .await;
+            assert_eq!(sharded, expected);
+
+            let states = SequentialBroker::default()
+                .process(deposits(&input))
+                .await
+                .accounts;
+            let sync = transaction_broker_sync(deposits(&input)).await;
+            assert_eq!(sync.collect::<Vec<_>>().await, states);
+            let actor = transaction_broker(deposits(&input)).await;
+            assert_eq!(actor.collect::<Vec<_>>().await, states);
+        })
+    }
+
+    #[test]
+    fn backpressure_limits_queues() {
+        rt::block_on(async {
+            // a single hot account
+            let input = vec![7; 2000];
+            let backpressure = Backpressure {
+                channel_capacity: Some(2),
+                max_in_flight: Some(4),
+                ..Backpressure::default()
+            };
+            let actor_broker = ActorBroker {
+                order: AccountOrder::ClientId,
+                backpressure: backpressure.clone(),
+            };
+
+            let bounded = actor_broker.process(deposits(&input)).await;
+            let unbounded = SequentialBroker::default().process(deposits(&input)).await;
+
+            assert_eq!(bounded, unbounded);
+            assert!(backpressure.metrics.max_queue_depth() <= 2);
+            assert!(backpressure.metrics.max_in_flight() <= 4);
+            assert_eq!(backpressure.metrics.in_flight(), 0);
+        })
+    }
+
+    #[test]
+    fn accounts_are_queried_while_running() {
+        rt::block_on(async {
+            let (input, transactions) = async_channel::unbounded();
+            let (handle, output) = ActorBroker::default().start(Box::pin(transactions));
+            let output = rt::spawn(output);
+
+            for tx in deposit_txs(&[1, 1, 2]) {
+                input.send(tx).await.unwrap();
+            }
+
+            // once all transactions are dispatched, every query reflects them
+            let snapshot = loop {
+                let snapshot = handle.snapshot().await.unwrap();
+                if snapshot.sequence == 3 {
+                    break snapshot;
+                }
+            };
+            let account = handle.account(AccountId(1)).await.unwrap();
+
+            assert_eq!(account.available, Amount::from_u64(2));
+            assert_eq!(handle.account(AccountId(9)).await, None);
+
+            input.close();
+            let output = output.await;
+
+            assert_eq!(snapshot.accounts, output.accounts);
+            assert_eq!(handle.snapshot().await, None);
+            assert_eq!(handle.account(AccountId(1)).await, None);
+        })
+    }
+
+    #[test]
+    fn snapshots_are_consistent() {
+        rt::block_on(async {
+            let account_ids = (0..2000).map(|i| (i * 7 % 13) as u16).collect::<Vec<_>>();
+            let txs = deposit_txs(&account_ids);
+            // applying one transaction at a time interleaves the snapshots with the input
+            let actor_broker = ActorBroker {
+                backpressure: Backpressure {
+                    channel_capacity: Some(1),
+                    max_in_flight: Some(1),
+                    ..Backpressure::default()
+                },
+                ..ActorBroker::default()
+            };
+
+            let (handle, output) = actor_broker.start(stream::iter(txs.clone()));
+            let snapshots = async {
+                let mut snapshots = Vec::new();
+                while let Some(snapshot) = handle.snapshot().await {
+                    snapshots.push(snapshot);
+                }
+                snapshots
+            };
+            let (_output, snapshots) = futures::join!(output, snapshots);
+
+            assert!(!snapshots.is_empty());
+
+            // replay the input, comparing every snapshot with the states at its sequence number
+            let mut engine = Engine::new(AccountOrder::ClientId);
+            let mut applied = 0;
+            for snapshot in snapshots {
+                for tx in &txs[applied..snapshot.sequence as usize] {
+                    engine.apply(tx.clone()).unwrap();
+                }
+                applied = snapshot.sequence as usize;
+
+                let expected = account_ids[..applied]
+                    .iter()
+                    .copied()
+                    .collect::<std::collections::BTreeSet<_>>()
+                    .into_iter()
+                    .map(|id| engine.account(&AccountId(id)).unwrap().clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(snapshot.accounts, expected);
+            }
+        })
+    }
+
+    #[test]
+    fn charge_back_events_lock_the_account() {
+        let mut transactions = deposit_txs(&[1, 2]);
+        transactions.extend([
+            Transaction::dispute(AccountId(1), TransactionId(0)),
+            Transaction::charge_back(AccountId(1), TransactionId(0)),
+            Transaction::dispute(AccountId(1), TransactionId(0)),
+        ]);
+
+        let broker = ActorBroker::default();
+
+        let (events, output) = broker.process_with_events(stream::iter(transactions));
+        let (events, output) = rt::block_on(future::join(events.collect::<Vec<_>>(), output));
+
+        let account_1 = events
+            .iter()
+            .filter(|event| event.event.account_id == AccountId(1))
+            .map(|event| (event.sequence, event.event.kind.clone()))
+            .collect::<Vec<_>>();
+        let amount = Amount::from_u64(1);
+
+        assert_eq!(
+            account_1,
+            [
+                (
+                    0,
+                    AccountEventKind::Deposited {
+                        amount: amount.clone()
+                    }
+                ),
+                (
+                    2,
+                    AccountEventKind::FundsHeld {
+                        amount: amount.clone()
+                    }
+                ),
+                (3, AccountEventKind::ChargedBack { amount }),
+                (3, AccountEventKind::AccountLocked),
+            ]
+        );
+        assert_eq!(events.len(), 5);
+        assert_eq!(output.rejections.len(), 1);
+    }
 }